@@ -0,0 +1,23 @@
+//! Compares walking a list with repeated `cdr` under the vector and cons cell
+//! representations. Run with `cargo bench --bench cdr`.
+use mlisp::types::Expr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+fn walk(list: Rc<Expr>) -> Duration {
+    let start = Instant::now();
+    let mut rest = list;
+    while let Some(tail) = rest.cdr() {
+        rest = tail;
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for &n in &[1_000usize, 5_000, 20_000] {
+        let xs: Vec<Rc<Expr>> = (0..n).map(|i| Expr::fnum(i as f64)).collect();
+        let vector = walk(Expr::list(&xs));
+        let pairs = walk(Expr::cons_list(&xs));
+        println!("cdr loop over {:>6} elements: vector {:>12?}  cons {:>12?}", n, vector, pairs);
+    }
+}
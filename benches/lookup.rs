@@ -0,0 +1,24 @@
+//! Times calls to a user function defined below many nested contexts, where each
+//! call has to walk the whole context stack. Run with `cargo bench --bench lookup`.
+use mlisp::eval::{eval, Environment};
+use mlisp::types::Expr;
+use std::time::Instant;
+
+const CALLS: usize = 2_000;
+
+fn main() {
+    for &depth in &[10usize, 100, 1_000] {
+        let mut env = Environment::default();
+        let body = Expr::list(&[Expr::symbol("+"), Expr::symbol("x"), Expr::fnum(1.0)]);
+        env.add_fn("inc", &["x".to_string()], body).expect("default environment has a context");
+        (0..depth).for_each(|_| env.push_context());
+        let call = Expr::list(&[Expr::symbol("inc"), Expr::fnum(1.0)]);
+
+        let start = Instant::now();
+        for _ in 0..CALLS {
+            eval(call.clone(), &mut env);
+        }
+        let elapsed = start.elapsed();
+        println!("{:>5} contexts: {:>12?} per call", depth, elapsed / CALLS as u32);
+    }
+}
@@ -1,14 +1,17 @@
 use std::env;
 use std::fs;
 use mlisp::interpreter::run_interpreter;
+use mlisp::repl::Repl;
+use std::io;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    println!("args: {:?}", args);
-    assert!(args.len() > 1, "Must supply a file path.");
+    if args.len() < 2 {
+        let stdin = io::stdin();
+        Repl::default().run(stdin.lock(), &mut io::stdout()).expect("There was an error running the REPL.");
+        return;
+    }
     let file = fs::read_to_string(&args[1]).expect("There was an error eading the file.");
 
-    println!("Read content: {}", file);
-
     run_interpreter(&file);
 }
@@ -1,6 +1,52 @@
-use crate::types::Expr;
+use crate::types::{as_f64_vec, count_nodes, Captured, Expr};
+use crate::lex::lex;
+use crate::parse::{parse, parse_program};
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Forms that control evaluation itself and therefore cannot be shadowed by user functions:
+/// those that define or change bindings, and those that decide when (or whether) their
+/// arguments are evaluated.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "fn", "defmacro", "let", "var", "set!", "undef", "let-values", "if", "while", "quote",
+    "function", "delay", "unit", "with-output", "deadline",
+];
+
+/// Builtin functions: they evaluate their arguments, so calls to them can be built from
+/// argument values, as `apply_fn` does when given one of these names.
+pub const BUILTINS: &[&str] = &[
+    "+", "-", "*", "/", "=", "!=", "<", ">", "<=", ">=", "not", "and", "or", "print", "eval", "map",
+    "apply", "char->number", "number->char", "string->list", "list->string", "random", "seed!",
+    "ast-size", "slice", "iterate", "values", "divmod", "flatten", "take", "drop", "count",
+    "enumerate", "force", "%", "mod-euclid", "pprint", "length", "reverse", "insert", "remove",
+    "approx=", "unfold", "partition", "every?", "some?", "make-counter", "sum", "product", "format",
+    "tree-approx=", "depth", "reduce", "chunk", "distinct", "product-pairs", "alist->map",
+    "map->alist", "minmax", "starts-with?", "ends-with?", "read-eval-string", "curry", "compose",
+    "repeat", "string->number-or", "bound?", "gcd", "lcm", "divides?", "factorial", "expt",
+    "values-of", "print-each", "paths", "list-depth", "car-or", "type-of", "swap", "fold-until",
+    "interleave", "lookup", "frequencies", "scan", "unit?", "transpose", "index-of", "map-pairs",
+    "map-indexed", "car", "cdr", "cons", "load",
+];
+
+/// Builtin operators that read as two-argument function values when they are not bound
+/// to anything, so they can be passed to higher-order builtins like `map` and `reduce`.
+pub const OPERATORS: &[&str] = &["+", "-", "*", "/", "%", "=", "!=", "<", ">", "<=", ">="];
+
+/// Maximum number of applications `iterate` performs before giving up.
+pub const MAX_ITERATIONS: usize = 10_000;
+
+/// Longest list `repeat` builds, so a huge count fails cleanly instead of exhausting memory.
+pub const MAX_LIST_LENGTH: usize = 1_000_000;
+
+/// Number of `eval` steps between checks of the wall-clock deadline.
+const DEADLINE_CHECK_INTERVAL: usize = 64;
+
+/// Error `tick` reports once `Environment::deadline` has passed.
+const TIMED_OUT: &str = "evaluation timed out";
+
+/// Seed used by `random` until the program calls `seed!`.
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
 
 #[derive(Debug, PartialEq)]
 pub enum EvalResult {
@@ -9,190 +55,2203 @@ pub enum EvalResult {
     Unit,
 }
 
-#[derive(Debug)]
-pub struct Environment {
-    pub contexts: Vec<HashMap<String, (Vec<String>, Rc<Expr>)>>,
+/// A single binding: the parameter names (empty for variables) and the body.
+pub type Binding = (Vec<String>, Rc<Expr>);
+
+/// A binding as a context stores it: the parameter names, the body, and whether `set!`
+/// may change it when `immutable_lets` is set, which only bindings made with `var` allow.
+pub type Entry = (Vec<String>, Rc<Expr>, bool);
+
+#[derive(Debug)]
+pub struct Environment {
+    pub contexts: Vec<HashMap<String, Entry>>,
+    /// Stack of buffers capturing printed output. Output goes to stdout when it is empty.
+    pub output_buffers: Vec<String>,
+    /// Maximum number of bytes `print` may write in total. Unlimited when `None`.
+    pub output_limit: Option<usize>,
+    /// Number of bytes written by `print` so far.
+    pub output_written: usize,
+    /// State of the xorshift generator behind `random`. Never zero.
+    pub rng_state: u64,
+    /// Maximum number of `eval` calls allowed. Unlimited when `None`.
+    pub step_limit: Option<usize>,
+    /// Number of `eval` calls made so far.
+    pub steps: usize,
+    /// Instant after which evaluation is aborted. No time limit when `None`.
+    pub deadline: Option<Instant>,
+    /// Macros defined with `defmacro`: their parameter names and the body producing the expansion.
+    pub macros: HashMap<String, Binding>,
+    /// When set, symbol names are lowercased on definition and lookup, so `Foo` and `foo` are
+    /// the same binding. Use `ignore_case` to turn it on for an environment that already has bindings.
+    pub case_insensitive: bool,
+    /// When set, defining a function or variable named after a special form is an error
+    /// rather than a warning.
+    pub strict: bool,
+    /// When set, `values-of` rejects unbound names instead of giving `()` for them.
+    pub strict_values_of: bool,
+    /// Warnings recorded during evaluation, oldest first.
+    pub warnings: Vec<String>,
+    /// When set, `let` bindings are immutable and `set!` only changes bindings made with `var`.
+    pub immutable_lets: bool,
+    /// When set, `eval` writes every expression it enters and every result it returns to the output.
+    pub trace: bool,
+    /// Nesting depth of the `eval` call currently being traced.
+    pub trace_depth: usize,
+}
+
+impl Environment {
+    pub fn empty() -> Environment {
+        Environment {
+            contexts: Vec::new(),
+            output_buffers: Vec::new(),
+            output_limit: None,
+            output_written: 0,
+            rng_state: DEFAULT_SEED,
+            step_limit: None,
+            steps: 0,
+            deadline: None,
+            macros: HashMap::new(),
+            case_insensitive: false,
+            strict: false,
+            strict_values_of: false,
+            warnings: Vec::new(),
+            immutable_lets: false,
+            trace: false,
+            trace_depth: 0,
+        }
+    }
+
+    /// An environment with a single, empty context: ready for bindings but without
+    /// the `True` and `False` that `default()` predefines.
+    pub fn bare() -> Environment {
+        Environment {
+            contexts: vec![HashMap::new()],
+            ..Environment::empty()
+        }
+    }
+
+    /// Helper function for tests
+    pub fn from_vars(vars: &[(&str, Rc<Expr>)]) -> Environment {
+        let mut env = Environment::empty();
+        env.push_context();
+        vars.iter().for_each(|(name, expr)| {
+            let _ = env.add_var(name, expr.clone());
+        });
+        env
+    }
+
+    /// Switches to case-insensitive names, rekeying the existing bindings such as
+    /// `True` and `False` so they stay reachable.
+    pub fn ignore_case(mut self) -> Environment {
+        self.case_insensitive = true;
+        self.contexts = self.contexts.into_iter()
+            .map(|cntxt| cntxt.into_iter().map(|(name, binding)| (name.to_lowercase(), binding)).collect())
+            .collect();
+        self
+    }
+
+    /// The key a symbol is stored under, which depends on `case_insensitive`.
+    fn key(&self, symbol: &str) -> String {
+        if self.case_insensitive {
+            symbol.to_lowercase()
+        } else {
+            symbol.to_string()
+        }
+    }
+
+    /// Looks up the given symbol in the Environment.
+    pub fn lookup(&self, symbol: &str) -> Option<Binding> {
+        let key = self.key(symbol);
+        self.contexts.iter().rev()
+            .find_map(|cntxt| cntxt.get(&key))
+            .map(|(params, body, _)| (params.clone(), body.clone()))
+    }
+
+    /// Checks whether the given symbol exists in the Environment.
+    pub fn contains_key(&self, symbol: &str) -> bool {
+        let key = self.key(symbol);
+        self.contexts.iter().rev()
+            .any(|cntxt| cntxt.contains_key(&key))
+    }
+
+    /// Pushes a new context on the `contexts` stack.
+    pub fn push_context(&mut self) {
+        self.contexts.push(HashMap::new());
+    }
+
+    /// Pops the last context from the `contexts` stack.
+    pub fn pop_context(&mut self) {
+        self.contexts.pop();
+    }
+
+    /// Releases the spare capacity left behind by pushing and popping many contexts, as
+    /// happens over a long REPL session. Returns the number of context slots plus map
+    /// entries of capacity that were given back.
+    pub fn compact(&mut self) -> usize {
+        let before = self.contexts.capacity();
+        self.contexts.shrink_to_fit();
+        let reclaimed_entries: usize = self.contexts.iter_mut()
+            .map(|cntxt| {
+                let before = cntxt.capacity();
+                cntxt.shrink_to_fit();
+                before - cntxt.capacity()
+            })
+            .sum();
+        before - self.contexts.capacity() + reclaimed_entries
+    }
+
+    /// Adds a variable definition to the Environment
+    pub fn add_var(&mut self, var: &str, val: Rc<Expr>) -> Result<(), String> {
+        self.insert_var(var, val, false)
+    }
+
+    /// Like `add_var`, but the binding stays changeable by `set!` when `immutable_lets` is set.
+    pub fn add_mutable_var(&mut self, var: &str, val: Rc<Expr>) -> Result<(), String> {
+        self.insert_var(var, val, true)
+    }
+
+    fn insert_var(&mut self, var: &str, val: Rc<Expr>, mutable: bool) -> Result<(), String> {
+        let key = self.key(var);
+        self.contexts.last_mut()
+            .map_or_else(
+                || Err("Environment has no context to add to.".into()),
+                |cntxt| { cntxt.insert(key, (Vec::new(), val.clone(), mutable)); Ok(()) },
+            )
+    }
+
+    /// Rebinds an existing variable in the innermost context that defines it.
+    ///
+    /// The binding gets the new `Rc`; values previously read from it are left untouched.
+    pub fn set_var(&mut self, var: &str, val: Rc<Expr>) -> Result<(), String> {
+        let key = self.key(var);
+        let immutable_lets = self.immutable_lets;
+        let entry = self.contexts.iter_mut().rev()
+            .find_map(|cntxt| cntxt.get_mut(&key))
+            .ok_or_else(|| format!("cannot set! unbound variable {}", var))?;
+        if immutable_lets && !entry.2 {
+            return Err(format!("cannot set! immutable binding {}, define it with var instead", var));
+        }
+        *entry = (Vec::new(), val, entry.2);
+        Ok(())
+    }
+
+    /// Removes the binding of `var` from the innermost context that defines it, uncovering
+    /// any binding of the same name in an outer context.
+    pub fn remove_var(&mut self, var: &str) -> Result<Binding, String> {
+        let key = self.key(var);
+        self.contexts.iter_mut().rev()
+            .find_map(|cntxt| cntxt.remove(&key))
+            .map(|(params, body, _)| (params, body))
+            .ok_or_else(|| format!("cannot undef unbound variable {}", var))
+    }
+
+    /// Adds a function definition to the Environment
+    pub fn add_fn(&mut self, name: &str, params: &[String], body: Rc<Expr>) -> Result<(), String> {
+        let key = self.key(name);
+        let param_names: Vec<String> = params.iter().map(|s| self.key(s)).collect();
+        self.contexts.last_mut().map_or(
+            Err("Environment does not have a context to add to.".into()),
+            |cntxt| {
+                cntxt.insert(key, (param_names, body.clone(), false));
+                Ok(())
+            },
+        )
+    }
+
+    /// Names of every binding visible from the innermost context, plus the `OPERATORS`,
+    /// which read as function values even when unbound, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.contexts.iter()
+            .flat_map(|cntxt| cntxt.keys().cloned())
+            .chain(OPERATORS.iter().map(|op| op.to_string()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn num_contexts(&self) -> usize {
+        self.contexts.len()
+    }
+
+    /// Writes printed output to the innermost capture buffer, or to stdout if there is none.
+    ///
+    /// Fails without writing anything once `output_limit` would be exceeded.
+    pub fn write_output(&mut self, text: &str) -> Result<(), String> {
+        if self.output_limit.is_some_and(|limit| self.output_written + text.len() > limit) {
+            return Err("output limit exceeded".into());
+        }
+        self.output_written += text.len();
+        match self.output_buffers.last_mut() {
+            Some(buf) => buf.push_str(text),
+            None => print!("{}", text),
+        }
+        Ok(())
+    }
+
+    /// Counts one evaluation step, failing once `step_limit` is crossed
+    /// or, checked every few steps, once `deadline` has passed.
+    pub fn tick(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.step_limit.is_some_and(|limit| self.steps > limit) {
+            return Err("step limit exceeded".into());
+        }
+        let check_deadline = self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL);
+        match self.deadline {
+            Some(deadline) if check_deadline && Instant::now() >= deadline => Err(TIMED_OUT.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reseeds the generator behind `random`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng_state = if seed == 0 { DEFAULT_SEED } else { seed };
+    }
+
+    /// Draws the next float in `[0, 1)` from the xorshift generator.
+    pub fn next_random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        let defaults: HashMap<String, Entry> = [
+            ("False".into(), (Vec::new(), Expr::list(&[]), false)), ("True".into(), (Vec::new(), Expr::list(&[Expr::fnum(1.0)]), false)),
+        ].iter().cloned().collect();
+        Environment{
+            contexts: vec![defaults],
+            ..Environment::empty()
+        }
+    }
+}
+
+/// A thin wrapper around a builtin operator: a function value of two arguments that applies `op` to them.
+pub fn operator_value(op: &str) -> Rc<Expr> {
+    let params = ["a".to_string(), "b".to_string()];
+    Expr::lambda(&params, Expr::list(&[Expr::symbol(op), Expr::symbol("a"), Expr::symbol("b")]))
+}
+
+fn eval_symbol(expr: Rc<Expr>, sym: &str, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let binding = env.lookup(sym);
+    eval_binding(expr, sym, binding, args, env)
+}
+
+/// Evaluates `sym` given its already looked up binding, so callers that needed the
+/// binding themselves do not walk the context stack a second time.
+fn eval_binding(expr: Rc<Expr>, sym: &str, binding: Option<Binding>, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    binding
+        .map_or_else(
+            || match OPERATORS.contains(&sym) {
+                true => EvalResult::Expr(operator_value(sym)),
+                false => EvalResult::Expr(expr),
+            },
+            |(param_names, expression)| {
+                if param_names.is_empty(){
+                    eval(expression.clone(), env)
+                } else {
+                    if args.len() != param_names.len() {
+                        return EvalResult::Err(format!("provided {} arguments but expected {}", args.len(), param_names.len()));
+                    }
+                    eval_args(args, env).map_or_else(
+                        EvalResult::Err,
+                        |argum| in_call_to(sym, call_fn(&param_names, expression.clone(), &argum, env)),
+                    )
+                }    
+            },
+        )
+}
+
+/// Evaluates each argument of a function call, rejecting Unit results.
+fn eval_args(args: &[Rc<Expr>], env: &mut Environment) -> Result<Vec<Rc<Expr>>, String> {
+    args.iter()
+        .map(|expr| match eval(expr.clone(), env) {
+           EvalResult::Expr(e) => Ok(e),
+           EvalResult::Err(err) => Err(err),
+           _ => Err("Cannot pass Unit as an argument to a function.".into()),
+        }).collect()
+}
+
+/// Binds the already evaluated `args` to `param_names` in a new context and evaluates `body`.
+fn call_fn(param_names: &[String], body: Rc<Expr>, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if args.len() != param_names.len() {
+        return EvalResult::Err(format!("provided {} arguments but expected {}", args.len(), param_names.len()));
+    }
+    env.push_context();
+    param_names.iter().zip(args).for_each(|(name, expr)| {
+        let _ = env.add_var(name, expr.clone());
+    });
+    let result = eval(body, env);
+    env.pop_context();
+    result
+}
+
+/// Calls a function value that closes over variables. They are bound, changeable by `set!`,
+/// in a context of their own around the call, and their final values are kept for the next call.
+fn call_closure(params: &[String], body: Rc<Expr>, captured: &Captured, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    env.push_context();
+    for (name, value) in captured.borrow().iter() {
+        if let Err(err) = env.add_mutable_var(name, value.clone()) {
+            env.pop_context();
+            return EvalResult::Err(err);
+        }
+    }
+    let result = call_fn(params, body, args, env);
+    for (name, value) in captured.borrow_mut().iter_mut() {
+        if let Some((_, current)) = env.lookup(name) {
+            *value = current;
+        }
+    }
+    env.pop_context();
+    result
+}
+
+/// Prefixes an error raised by the body of the named function with the function's name,
+/// so errors from nested calls read like a backtrace.
+fn in_call_to(name: &str, result: EvalResult) -> EvalResult {
+    match result {
+        EvalResult::Err(err) => EvalResult::Err(format!("in call to {}: {}", name, err)),
+        r => r,
+    }
+}
+
+/// Applies a callable value to already evaluated arguments.
+///
+/// A callable is either a function value (`Expr::Lambda` or `Expr::Partial`), the name
+/// of a user defined function, or the name of one of the `BUILTINS`, such as `+`.
+pub fn apply_fn(f: Rc<Expr>, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match &*f {
+        Expr::Lambda(params, body, captured) if captured.borrow().is_empty() => call_fn(params, body.clone(), args, env),
+        Expr::Lambda(params, body, captured) => call_closure(params, body.clone(), captured, args, env),
+        Expr::Partial(g, given) => {
+            let all: Vec<Rc<Expr>> = given.iter().chain(args).cloned().collect();
+            apply_fn(g.clone(), &all, env)
+        },
+        Expr::Symbol(s) => match env.lookup(s) {
+            Some((params, body)) if !params.is_empty() => in_call_to(s, call_fn(&params, body, args, env)),
+            None if BUILTINS.contains(&s.as_str()) => {
+                // Builtins evaluate their arguments, so quote them to avoid evaluating them twice.
+                let mut call = vec![f.clone()];
+                call.extend(args.iter().map(|a| Expr::list(&[Expr::symbol("quote"), a.clone()])));
+                eval(Expr::list(&call), env)
+            },
+            _ => EvalResult::Err(format!("{} is not a function.", s)),
+        },
+        _ => EvalResult::Err(format!("Expected a function, got {:?}", f)),
+    }
+}
+
+/// Evaluates the single argument of a unary builtin.
+fn eval_unary(name: &str, vals: &[Rc<Expr>], env: &mut Environment) -> Result<Rc<Expr>, String> {
+    if vals.len() != 1 {
+        return Err(format!("{} takes exactly one argument, got {}", name, vals.len()));
+    }
+    match eval(vals[0].clone(), env) {
+        EvalResult::Expr(e) => Ok(e),
+        EvalResult::Unit => Err(format!("{} cannot take Unit as an argument.", name)),
+        EvalResult::Err(err) => Err(err),
+    }
+}
+
+/// Returns the elements of a list or chain of pairs, or the uniform error list builtins report otherwise.
+fn expect_list(expr: &Expr, op: &str) -> Result<Vec<Rc<Expr>>, String> {
+    expr.items().ok_or_else(|| format!("{}: expected a list, got {:?}", op, expr))
+}
+
+fn car(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("car", vals, env).map_or_else(
+        EvalResult::Err,
+        |lst| match &*lst {
+            Expr::List(xs) if !xs.is_empty() => EvalResult::Expr(xs[0].clone()),
+            Expr::Pair(head, _) => EvalResult::Expr(head.clone()),
+            Expr::List(_) => EvalResult::Err("car: expected a non-empty list, got ()".into()),
+            _ => EvalResult::Err(format!("car: expected a list, got {:?}", lst)),
+        },
+    )
+}
+
+/// Vector lists are converted to cons cells here, so that repeatedly taking
+/// the `cdr` of the result does not copy the tail each time.
+fn cdr(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("cdr", vals, env).map_or_else(
+        EvalResult::Err,
+        |lst| match &*lst {
+            Expr::List(xs) if !xs.is_empty() => EvalResult::Expr(Expr::cons_list(&xs[1..])),
+            Expr::Pair(_, tail) => EvalResult::Expr(tail.clone()),
+            Expr::List(_) => EvalResult::Err("cdr: expected a non-empty list, got ()".into()),
+            _ => EvalResult::Err(format!("cdr: expected a list, got {:?}", lst)),
+        },
+    )
+}
+
+fn cons(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("cons must follow the pattern (cons <expr> <list>)".into());
+    }
+    match eval_args(vals, env) {
+        Err(err) => EvalResult::Err(err),
+        // Only the variant is checked: walking a chain of pairs would make `cons` linear.
+        Ok(args) if matches!(&*args[1], Expr::List(_) | Expr::Pair(..)) => {
+            EvalResult::Expr(Expr::cons(args[0].clone(), args[1].clone()))
+        },
+        Ok(args) => EvalResult::Err(format!("cons: expected a list, got {:?}", args[1])),
+    }
+}
+
+fn with_output(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("with-output must follow the pattern (with-output <expr>)".into());
+    }
+    env.output_buffers.push(String::new());
+    let result = eval(vals[0].clone(), env);
+    let captured = env.output_buffers.pop().unwrap_or_default();
+    match result {
+        EvalResult::Err(err) => EvalResult::Err(err),
+        _ => EvalResult::Expr(Expr::string(captured.strip_suffix('\n').unwrap_or(&captured))),
+    }
+}
+
+fn char_to_number(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("char->number", vals, env).map_or_else(
+        EvalResult::Err,
+        |c| match &*c {
+            Expr::Char(c) => EvalResult::Expr(Expr::fnum(*c as u32 as f64)),
+            _ => EvalResult::Err(format!("char->number expects a char, got {:?}", c)),
+        },
+    )
+}
+
+fn number_to_char(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("number->char", vals, env).map_or_else(
+        EvalResult::Err,
+        |n| match &*n {
+            Expr::FNum(x) if x.fract() == 0.0 && *x >= 0.0 && *x <= u32::MAX as f64 => {
+                std::char::from_u32(*x as u32).map_or_else(
+                    || EvalResult::Err(format!("{} is not a valid codepoint", x)),
+                    |c| EvalResult::Expr(Expr::character(c)),
+                )
+            },
+            _ => EvalResult::Err(format!("number->char expects a non-negative integer, got {:?}", n)),
+        },
+    )
+}
+
+fn string_to_list(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("string->list", vals, env).map_or_else(
+        EvalResult::Err,
+        |s| match &*s {
+            Expr::Str(s) => EvalResult::Expr(Expr::list(&s.chars().map(Expr::character).collect::<Vec<_>>())),
+            _ => EvalResult::Err(format!("string->list expects a string, got {:?}", s)),
+        },
+    )
+}
+
+fn list_to_string(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("list->string", vals, env).map_or_else(
+        EvalResult::Err,
+        |lst| expect_list(&lst, "list->string")
+            .and_then(|xs| xs.iter()
+                .map(|x| match &**x {
+                    Expr::Char(c) => Ok(*c),
+                    _ => Err(format!("list->string expects a list of chars, got {:?}", x)),
+                })
+                .collect::<Result<String, String>>())
+            .map_or_else(EvalResult::Err, |s| EvalResult::Expr(Expr::string(&s))),
+    )
+}
+
+fn random(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.is_empty() {
+        return EvalResult::Expr(Expr::fnum(env.next_random()));
+    }
+    eval_unary("random", vals, env).map_or_else(
+        EvalResult::Err,
+        |n| match &*n {
+            Expr::FNum(x) if *x >= 1.0 && x.fract() == 0.0 => {
+                EvalResult::Expr(Expr::fnum((env.next_random() * x).floor()))
+            },
+            _ => EvalResult::Err(format!("random expects a positive integer, got {:?}", n)),
+        },
+    )
+}
+
+fn seed_rng(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("seed!", vals, env).map_or_else(
+        EvalResult::Err,
+        |n| match &*n {
+            Expr::FNum(x) if x.fract() == 0.0 => {
+                env.seed_rng(*x as i64 as u64);
+                EvalResult::Unit
+            },
+            _ => EvalResult::Err(format!("seed! expects an integer, got {:?}", n)),
+        },
+    )
+}
+
+fn ast_size(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("ast-size", vals, env).map_or_else(
+        EvalResult::Err,
+        |e| EvalResult::Expr(Expr::fnum(count_nodes(&e) as f64)),
+    )
+}
+
+/// Reads an integer-valued number argument for the builtin `op`.
+fn as_integer(op: &str, e: &Expr) -> Result<i64, String> {
+    match e {
+        Expr::FNum(n) if n.fract() == 0.0 => Ok(*n as i64),
+        _ => Err(format!("{}: expected an integer, got {:?}", op, e)),
+    }
+}
+
+/// Resolves a Python style index against a list of length `len`. Negative
+/// indices count from the end, and out of range indices clamp to the list.
+fn clamp_index(index: i64, len: usize) -> usize {
+    let len = len as i64;
+    let i = if index < 0 { len + index } else { index };
+    i.clamp(0, len) as usize
+}
+
+/// `(slice lst start [end])` returns the elements from `start` up to but not
+/// including `end`, or to the end of the list when `end` is omitted. Indices
+/// may be negative and are clamped rather than reported as errors.
+fn slice(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 && vals.len() != 3 {
+        return EvalResult::Err("slice must follow the pattern (slice <list> <start> [end])".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let xs = match expect_list(&args[0], "slice") {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let bounds = as_integer("slice", &args[1]).and_then(|start| match args.get(2) {
+        Some(end) => as_integer("slice", end).map(|end| (start, end)),
+        None => Ok((start, xs.len() as i64)),
+    });
+    match bounds {
+        Ok((start, end)) => {
+            let (start, end) = (clamp_index(start, xs.len()), clamp_index(end, xs.len()));
+            EvalResult::Expr(Expr::list(if start < end { &xs[start..end] } else { &[] }))
+        },
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// Applies `f` to the already evaluated `args`, expecting a value back.
+fn apply_for_value(op: &str, f: Rc<Expr>, args: &[Rc<Expr>], env: &mut Environment) -> Result<Rc<Expr>, String> {
+    match apply_fn(f, args, env) {
+        EvalResult::Expr(e) => Ok(e),
+        EvalResult::Unit => Err(format!("{}: function returned Unit.", op)),
+        EvalResult::Err(err) => Err(err),
+    }
+}
+
+/// `(iterate f x pred)` applies `f` to `x` until `pred` holds for the current value.
+fn iterate(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("iterate must follow the pattern (iterate <function> <value> <predicate>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let (f, pred) = (args[0].clone(), args[2].clone());
+    let mut value = args[1].clone();
+    for _ in 0..MAX_ITERATIONS {
+        match apply_for_value("iterate", pred.clone(), std::slice::from_ref(&value), env) {
+            Ok(done) if is_truthy(&done) => return EvalResult::Expr(value),
+            Ok(_) => {},
+            Err(err) => return EvalResult::Err(err),
+        }
+        value = match apply_for_value("iterate", f.clone(), std::slice::from_ref(&value), env) {
+            Ok(next) => next,
+            Err(err) => return EvalResult::Err(err),
+        };
+    }
+    EvalResult::Err(format!("iterate: predicate not satisfied after {} iterations", MAX_ITERATIONS))
+}
+
+fn values(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_args(vals, env).map_or_else(EvalResult::Err, |xs| EvalResult::Expr(Expr::values(&xs)))
+}
+
+/// Unpacks a `values` result. Any other value counts as a single value.
+fn unpack_values(e: &Rc<Expr>) -> Vec<Rc<Expr>> {
+    match &**e {
+        Expr::Values(xs) => xs.clone(),
+        _ => vec![e.clone()],
+    }
+}
+
+fn divmod(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("divmod must follow the pattern (divmod <dividend> <divisor>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match (&*args[0], &*args[1]) {
+        (Expr::FNum(_), Expr::FNum(d)) if *d == 0.0 => EvalResult::Err("divmod: division by zero".into()),
+        (Expr::FNum(n), Expr::FNum(d)) => {
+            let q = (n / d).floor();
+            values(&[Expr::fnum(q), Expr::fnum(n - d * q)], env)
+        },
+        _ => EvalResult::Err(format!("divmod expects two numbers, got {:?} and {:?}", args[0], args[1])),
+    }
+}
+
+/// `(let-values ((a b ...) expr) body)` binds each value produced by `expr` while evaluating `body`.
+fn let_values(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let usage = "let-values must follow the pattern (let-values ((name1 name2 ...) <expr>) <body>)";
+    if vals.len() != 2 {
+        return EvalResult::Err(usage.into());
+    }
+    let (names, expr) = match &*vals[0] {
+        Expr::List(binding) if binding.len() == 2 => match &*binding[0] {
+            Expr::List(names) => (names, binding[1].clone()),
+            _ => return EvalResult::Err(usage.into()),
+        },
+        _ => return EvalResult::Err(usage.into()),
+    };
+    let names: Result<Vec<String>, String> = names.iter().map(|n| match &**n {
+        Expr::Symbol(s) => Ok(s.clone()),
+        _ => Err(format!("variable name must be a symbol, got {:?}", n)),
+    }).collect();
+    let names = match names {
+        Ok(names) => names,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match eval(expr, env) {
+        EvalResult::Expr(e) => {
+            let xs = unpack_values(&e);
+            if xs.len() != names.len() {
+                return EvalResult::Err(format!("let-values: expected {} values, got {}", names.len(), xs.len()));
+            }
+            call_fn(&names, vals[1].clone(), &xs, env)
+        },
+        EvalResult::Unit => EvalResult::Err("let-values: expression returned Unit.".into()),
+        err => err,
+    }
+}
+
+fn flatten_into(e: &Rc<Expr>, out: &mut Vec<Rc<Expr>>) {
+    match e.items() {
+        Some(xs) => xs.iter().for_each(|x| flatten_into(x, out)),
+        None => out.push(e.clone()),
+    }
+}
+
+fn flatten(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("flatten", vals, env).map_or_else(
+        EvalResult::Err,
+        |lst| match expect_list(&lst, "flatten") {
+            Ok(_) => {
+                let mut leaves = Vec::new();
+                flatten_into(&lst, &mut leaves);
+                EvalResult::Expr(Expr::list(&leaves))
+            },
+            Err(err) => EvalResult::Err(err),
+        },
+    )
+}
+
+/// Shared by `take` and `drop`: splits the list after `n` elements and keeps one side.
+fn split_list(op: &str, vals: &[Rc<Expr>], env: &mut Environment, keep_front: bool) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err(format!("{} must follow the pattern ({} <n> <list>)", op, op));
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let n = match as_integer(op, &args[0]) {
+        Ok(n) if n < 0 => return EvalResult::Err(format!("{}: count must not be negative, got {}", op, n)),
+        Ok(n) => n as usize,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match expect_list(&args[1], op) {
+        Ok(xs) => {
+            let (front, back) = xs.split_at(n.min(xs.len()));
+            EvalResult::Expr(Expr::list(if keep_front { front } else { back }))
+        },
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `(count value list)` counts the elements of `list` that are `=` to `value`.
+fn count(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("count must follow the pattern (count <value> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "count").map_or_else(
+        EvalResult::Err,
+        |xs| EvalResult::Expr(Expr::fnum(xs.iter().filter(|x| args[0].eq(x)).count() as f64)),
+    )
+}
+
+/// `(enumerate list)` pairs each element with its index: `((0 a) (1 b) ...)`.
+fn enumerate(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("enumerate", vals, env).map_or_else(
+        EvalResult::Err,
+        |lst| expect_list(&lst, "enumerate").map_or_else(
+            EvalResult::Err,
+            |xs| {
+                let pairs: Vec<Rc<Expr>> = xs.iter().enumerate()
+                    .map(|(i, x)| Expr::list(&[Expr::fnum(i as f64), x.clone()]))
+                    .collect();
+                EvalResult::Expr(Expr::list(&pairs))
+            },
+        ),
+    )
+}
+
+fn delay(vals: &[Rc<Expr>]) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err(format!("delay takes exactly one argument, got {}", vals.len()));
+    }
+    EvalResult::Expr(Expr::promise(vals[0].clone()))
+}
+
+/// Evaluates a promise's expression the first time it is forced and returns the cached value after that.
+/// Forcing anything other than a promise returns it unchanged.
+fn force(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let promise = match eval_unary("force", vals, env) {
+        Ok(promise) => promise,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let (delayed, cache) = match &*promise {
+        Expr::Promise(delayed, cache) => (delayed, cache),
+        _ => return EvalResult::Expr(promise),
+    };
+    if let Some(value) = cache.borrow().clone() {
+        return EvalResult::Expr(value);
+    }
+    match eval(delayed.clone(), env) {
+        EvalResult::Expr(value) => {
+            *cache.borrow_mut() = Some(value.clone());
+            EvalResult::Expr(value)
+        },
+        EvalResult::Unit => EvalResult::Err("force: delayed expression returned Unit.".into()),
+        err => err,
+    }
+}
+
+/// Shared by `%`, whose result takes the sign of the dividend, and `mod-euclid`, whose result is never negative.
+fn remainder(op: &str, vals: &[Rc<Expr>], env: &mut Environment, rem: fn(f64, f64) -> f64) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err(format!("{} must follow the pattern ({} <dividend> <divisor>)", op, op));
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match (&*args[0], &*args[1]) {
+        (Expr::FNum(_), Expr::FNum(d)) if *d == 0.0 => EvalResult::Err(format!("{}: division by zero", op)),
+        (Expr::FNum(n), Expr::FNum(d)) => EvalResult::Expr(Expr::fnum(rem(*n, *d))),
+        _ => EvalResult::Err(format!("{} expects two numbers, got {:?} and {:?}", op, args[0], args[1])),
+    }
+}
+
+fn length(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("length", vals, env)
+        .and_then(|lst| expect_list(&lst, "length"))
+        .map_or_else(EvalResult::Err, |xs| EvalResult::Expr(Expr::fnum(xs.len() as f64)))
+}
+
+fn reverse(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("reverse", vals, env)
+        .and_then(|lst| expect_list(&lst, "reverse"))
+        .map_or_else(EvalResult::Err, |xs| {
+            let reversed: Vec<Rc<Expr>> = xs.into_iter().rev().collect();
+            EvalResult::Expr(Expr::list(&reversed))
+        })
+}
+
+/// Checks that `index` is below `bound` and converts it, reporting the list length otherwise.
+fn list_index(op: &str, index: &Expr, bound: usize, len: usize) -> Result<usize, String> {
+    match as_integer(op, index)? {
+        i if i >= 0 && (i as usize) < bound => Ok(i as usize),
+        i => Err(format!("{}: index {} out of range for list of length {}", op, i, len)),
+    }
+}
+
+/// `(insert i x list)` returns a copy of `list` with `x` inserted before index `i`.
+fn insert(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("insert must follow the pattern (insert <index> <value> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[2], "insert")
+        .and_then(|xs| list_index("insert", &args[0], xs.len() + 1, xs.len()).map(|i| (i, xs)))
+        .map_or_else(EvalResult::Err, |(i, mut xs)| {
+            xs.insert(i, args[1].clone());
+            EvalResult::Expr(Expr::list(&xs))
+        })
+}
+
+/// `(remove i list)` returns a copy of `list` without the element at index `i`.
+fn remove(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("remove must follow the pattern (remove <index> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "remove")
+        .and_then(|xs| list_index("remove", &args[0], xs.len(), xs.len()).map(|i| (i, xs)))
+        .map_or_else(EvalResult::Err, |(i, mut xs)| {
+            xs.remove(i);
+            EvalResult::Expr(Expr::list(&xs))
+        })
+}
+
+/// Tolerance `approx=` uses when none is given.
+pub const DEFAULT_EPSILON: f64 = 1e-9;
+
+/// `(approx= a b [epsilon])` is True when `|a - b| <= epsilon`.
+fn approx_equal(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 && vals.len() != 3 {
+        return EvalResult::Err("approx= must follow the pattern (approx= <a> <b> [epsilon])".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let nums: Result<Vec<f64>, String> = args.iter().map(|x| match &**x {
+        Expr::FNum(n) => Ok(*n),
+        _ => Err(format!("approx= expects numbers, got {:?}", x)),
+    }).collect();
+    nums.map_or_else(EvalResult::Err, |nums| {
+        let epsilon = nums.get(2).copied().unwrap_or(DEFAULT_EPSILON);
+        let equal = (nums[0] - nums[1]).abs() <= epsilon;
+        EvalResult::Expr(Expr::symbol(if equal { "True" } else { "False" }))
+    })
+}
+
+/// `(unfold f seed stop?)` collects values while `stop?` of the seed is falsey. `f` maps a
+/// seed to its value and the next seed, either as `(values value next)` or as a two-element list.
+fn unfold(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("unfold must follow the pattern (unfold <function> <seed> <predicate>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let (f, stop) = (args[0].clone(), args[2].clone());
+    let mut seed = args[1].clone();
+    let mut collected = Vec::new();
+    for _ in 0..MAX_ITERATIONS {
+        match apply_for_value("unfold", stop.clone(), std::slice::from_ref(&seed), env) {
+            Ok(done) if is_truthy(&done) => return EvalResult::Expr(Expr::list(&collected)),
+            Ok(_) => {},
+            Err(err) => return EvalResult::Err(err),
+        }
+        let step = match apply_for_value("unfold", f.clone(), std::slice::from_ref(&seed), env) {
+            Ok(step) => step,
+            Err(err) => return EvalResult::Err(err),
+        };
+        let parts = match unpack_values(&step) {
+            parts if parts.len() == 2 => parts,
+            _ => step.items().unwrap_or_default(),
+        };
+        match parts.as_slice() {
+            [value, next] => {
+                collected.push(value.clone());
+                seed = next.clone();
+            },
+            _ => return EvalResult::Err(format!("unfold: function must return a value and the next seed, got {:?}", step)),
+        }
+    }
+    EvalResult::Err(format!("unfold: predicate not satisfied after {} iterations", MAX_ITERATIONS))
+}
+
+/// `(partition pred list)` splits `list` into `(matching non-matching)`, keeping the order of each.
+fn partition(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("partition must follow the pattern (partition <predicate> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let xs = match expect_list(&args[1], "partition") {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let (mut matching, mut rest) = (Vec::new(), Vec::new());
+    for x in xs {
+        match apply_for_value("partition", args[0].clone(), std::slice::from_ref(&x), env) {
+            Ok(keep) if is_truthy(&keep) => matching.push(x),
+            Ok(_) => rest.push(x),
+            Err(err) => return EvalResult::Err(err),
+        }
+    }
+    EvalResult::Expr(Expr::list(&[Expr::list(&matching), Expr::list(&rest)]))
+}
+
+/// `(deadline ms body)` evaluates `body`, failing with "deadline exceeded" if it takes longer
+/// than `ms` milliseconds. An earlier deadline set by the host or an outer `deadline` still applies.
+fn deadline(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("deadline must follow the pattern (deadline <ms> <body>)".into());
+    }
+    let ms = match eval(vals[0].clone(), env) {
+        EvalResult::Expr(ms) => match as_integer("deadline", &ms) {
+            Ok(ms) if ms >= 0 => ms as u64,
+            Ok(ms) => return EvalResult::Err(format!("deadline: milliseconds must not be negative, got {}", ms)),
+            Err(err) => return EvalResult::Err(err),
+        },
+        EvalResult::Unit => return EvalResult::Err("deadline: milliseconds cannot be Unit.".into()),
+        err => return err,
+    };
+    let inner = Instant::now() + Duration::from_millis(ms);
+    let outer = env.deadline;
+    let ours = outer.is_none_or(|outer| inner < outer);
+    if ours {
+        env.deadline = Some(inner);
+    }
+    let result = eval(vals[1].clone(), env);
+    env.deadline = outer;
+    match result {
+        EvalResult::Err(err) if ours && err.contains(TIMED_OUT) => EvalResult::Err(err.replace(TIMED_OUT, "deadline exceeded")),
+        r => r,
+    }
+}
+
+/// Shared by `every?` and `some?`: applies `pred` to each element until it returns
+/// `stop_on`, and returns whether it did.
+fn any_with(op: &str, vals: &[Rc<Expr>], env: &mut Environment, stop_on: bool) -> Result<bool, String> {
+    if vals.len() != 2 {
+        return Err(format!("{} must follow the pattern ({} <predicate> <list>)", op, op));
+    }
+    let args = eval_args(vals, env)?;
+    for x in expect_list(&args[1], op)? {
+        let result = apply_for_value(op, args[0].clone(), std::slice::from_ref(&x), env)?;
+        if is_truthy(&result) == stop_on {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn every(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    any_with("every?", vals, env, false).map_or_else(
+        EvalResult::Err,
+        |found_falsey| EvalResult::Expr(Expr::symbol(if found_falsey { "False" } else { "True" })),
+    )
+}
+
+fn some(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    any_with("some?", vals, env, true).map_or_else(
+        EvalResult::Err,
+        |found_truthy| EvalResult::Expr(Expr::symbol(if found_truthy { "True" } else { "False" })),
+    )
+}
+
+/// `(make-counter)` returns a function of no arguments that closes over a call count,
+/// starting at 0, and returns the count after incrementing it.
+fn make_counter(vals: &[Rc<Expr>]) -> EvalResult {
+    if !vals.is_empty() {
+        return EvalResult::Err(format!("make-counter takes no arguments, got {}", vals.len()));
+    }
+    // (set! calls (+ calls 1))
+    let body = Expr::list(&[
+        Expr::symbol("set!"),
+        Expr::symbol("calls"),
+        Expr::list(&[Expr::symbol("+"), Expr::symbol("calls"), Expr::fnum(1.0)]),
+    ]);
+    EvalResult::Expr(Expr::closure(&[], body, &[("calls".into(), Expr::fnum(0.0))]))
+}
+
+/// Shared by `sum` and `product`: folds the numbers of a single list argument, starting from `identity`.
+fn fold_numbers(op: &str, vals: &[Rc<Expr>], env: &mut Environment, identity: f64, combine: fn(f64, f64) -> f64) -> EvalResult {
+    eval_unary(op, vals, env)
+        .and_then(|lst| expect_list(&lst, op))
+        .and_then(|xs| xs.iter().enumerate().try_fold(identity, |acc, (i, x)| match &**x {
+            Expr::FNum(n) => Ok(combine(acc, *n)),
+            _ => Err(format!("{}: element {} is not a number, got {:?}", op, i, x)),
+        }))
+        .map_or_else(EvalResult::Err, |total| EvalResult::Expr(Expr::fnum(total)))
+}
+
+/// `(format template args...)` replaces each `{}` in the template with the printed form
+/// of the next argument. `{{` and `}}` stand for literal braces.
+fn format_string(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.is_empty() {
+        return EvalResult::Err("format must follow the pattern (format <template> <arg> ...)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let template = match &*args[0] {
+        Expr::Str(s) => s.clone(),
+        _ => return EvalResult::Err(format!("format expects a template string, got {:?}", args[0])),
+    };
+    let mut rest = args[1..].iter();
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                out.push(c);
+            },
+            ('{', Some('}')) => {
+                chars.next();
+                match rest.next() {
+                    Some(arg) => out.push_str(&flat(arg)),
+                    None => return EvalResult::Err(format!("format: too few arguments, got {}", args.len() - 1)),
+                }
+            },
+            ('{', _) | ('}', _) => return EvalResult::Err(format!("format: unmatched {} in template", c)),
+            _ => out.push(c),
+        }
+    }
+    if rest.next().is_some() {
+        return EvalResult::Err(format!("format: too many arguments, got {}", args.len() - 1));
+    }
+    EvalResult::Expr(Expr::string(&out))
+}
+
+/// Compares two trees of the same shape, allowing numbers to differ by `epsilon`. Other leaves must be equal.
+fn tree_approx_eq(a: &Expr, b: &Expr, epsilon: f64) -> bool {
+    match (a, b) {
+        (Expr::FNum(x), Expr::FNum(y)) => (x - y).abs() <= epsilon,
+        _ => match (a.items(), b.items()) {
+            (Some(xs), Some(ys)) => xs.len() == ys.len()
+                && xs.iter().zip(&ys).all(|(x, y)| tree_approx_eq(x, y, epsilon)),
+            (None, None) => a == b,
+            _ => false,
+        },
+    }
+}
+
+/// `(tree-approx= a b [epsilon])` is `approx=` extended to nested lists of the same shape.
+fn tree_approx_equal(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 && vals.len() != 3 {
+        return EvalResult::Err("tree-approx= must follow the pattern (tree-approx= <a> <b> [epsilon])".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let epsilon = match args.get(2).map(|e| &**e) {
+        None => DEFAULT_EPSILON,
+        Some(Expr::FNum(e)) => *e,
+        Some(e) => return EvalResult::Err(format!("tree-approx= expects a numeric epsilon, got {:?}", e)),
+    };
+    let equal = tree_approx_eq(&args[0], &args[1], epsilon);
+    EvalResult::Expr(Expr::symbol(if equal { "True" } else { "False" }))
+}
+
+fn depth(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if !vals.is_empty() {
+        return EvalResult::Err(format!("depth takes no arguments, got {}", vals.len()));
+    }
+    EvalResult::Expr(Expr::fnum(env.num_contexts() as f64))
+}
+
+/// `(reduce f init list)` folds `list` from the left: `f` is called with the running
+/// value, starting at `init`, and each element in turn.
+fn reduce(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("reduce must follow the pattern (reduce <function> <initial> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[2], "reduce")
+        .and_then(|xs| xs.into_iter().try_fold(args[1].clone(), |acc, x| {
+            apply_for_value("reduce", args[0].clone(), &[acc, x], env)
+        }))
+        .map_or_else(EvalResult::Err, EvalResult::Expr)
+}
+
+/// `(chunk n list)` splits `list` into sublists of `n` elements; the last one may be shorter.
+fn chunk(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("chunk must follow the pattern (chunk <n> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let n = match as_integer("chunk", &args[0]) {
+        Ok(n) if n <= 0 => return EvalResult::Err(format!("chunk: size must be positive, got {}", n)),
+        Ok(n) => n as usize,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "chunk").map_or_else(
+        EvalResult::Err,
+        |xs| EvalResult::Expr(Expr::list(&xs.chunks(n).map(Expr::list).collect::<Vec<_>>())),
+    )
+}
+
+/// `(distinct list)` drops every element `=` to an earlier one, keeping first occurrences in order.
+fn distinct(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("distinct", vals, env)
+        .and_then(|lst| expect_list(&lst, "distinct"))
+        .map_or_else(EvalResult::Err, |xs| {
+            let mut kept: Vec<Rc<Expr>> = Vec::new();
+            for x in xs {
+                if !kept.contains(&x) {
+                    kept.push(x);
+                }
+            }
+            EvalResult::Expr(Expr::list(&kept))
+        })
+}
+
+/// `(product-pairs a b)` lists every `(x y)` with `x` from `a` and `y` from `b`, varying `y` fastest.
+fn product_pairs(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("product-pairs must follow the pattern (product-pairs <list> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[0], "product-pairs")
+        .and_then(|xs| expect_list(&args[1], "product-pairs").map(|ys| (xs, ys)))
+        .map_or_else(EvalResult::Err, |(xs, ys)| {
+            let pairs: Vec<Rc<Expr>> = xs.iter()
+                .flat_map(|x| ys.iter().map(move |y| Expr::list(&[x.clone(), y.clone()])))
+                .collect();
+            EvalResult::Expr(Expr::list(&pairs))
+        })
+}
+
+/// `(alist->map alist)` builds a map from a list of `(key value)` pairs. When a key
+/// appears more than once the last pair wins.
+fn alist_to_map(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("alist->map", vals, env)
+        .and_then(|lst| expect_list(&lst, "alist->map"))
+        .and_then(|pairs| pairs.iter().enumerate().map(|(i, pair)| match pair.items() {
+            Some(kv) if kv.len() == 2 => Ok((kv[0].clone(), kv[1].clone())),
+            _ => Err(format!("alist->map: element {} is not a (key value) pair, got {:?}", i, pair)),
+        }).collect::<Result<Vec<_>, String>>())
+        .map_or_else(EvalResult::Err, |entries| EvalResult::Expr(Expr::map(&entries)))
+}
+
+/// `(map->alist map)` lists the entries of a map as `(key value)` pairs in insertion order.
+fn map_to_alist(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match eval_unary("map->alist", vals, env) {
+        Ok(m) => match &*m {
+            Expr::Map(entries) => {
+                let pairs: Vec<Rc<Expr>> = entries.iter().map(|(k, v)| Expr::list(&[k.clone(), v.clone()])).collect();
+                EvalResult::Expr(Expr::list(&pairs))
+            },
+            _ => EvalResult::Err(format!("map->alist: expected a map, got {:?}", m)),
+        },
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `(minmax list)` returns `(min max)` of a non-empty list of numbers in one pass.
+fn minmax(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let ns = match eval_unary("minmax", vals, env).and_then(|lst| as_f64_vec(&lst).map_err(|err| format!("minmax: {}", err))) {
+        Ok(ns) => ns,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match ns.split_first() {
+        Some((first, rest)) => {
+            let (lo, hi) = rest.iter().fold((*first, *first), |(lo, hi), &n| (lo.min(n), hi.max(n)));
+            EvalResult::Expr(Expr::list(&[Expr::fnum(lo), Expr::fnum(hi)]))
+        },
+        None => EvalResult::Err("minmax: empty list has no minimum or maximum".into()),
+    }
+}
+
+/// Shared by `starts-with?` and `ends-with?`: whether the first list is `=`, element by
+/// element, to the front (or back) of the second.
+fn affix_of(op: &str, vals: &[Rc<Expr>], env: &mut Environment, at_front: bool) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err(format!("{} must follow the pattern ({} <list> <list>)", op, op));
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[0], op)
+        .and_then(|affix| expect_list(&args[1], op).map(|xs| (affix, xs)))
+        .map_or_else(EvalResult::Err, |(affix, xs)| {
+            let matches = if at_front { xs.starts_with(&affix) } else { xs.ends_with(&affix) };
+            EvalResult::Expr(Expr::symbol(if matches { "True" } else { "False" }))
+        })
+}
+
+/// `(read-eval-string source)` lexes, parses and evaluates a single form from a string in
+/// the current environment. The evaluation counts toward the same step limit and deadline.
+fn read_eval_string(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let source = match eval_unary("read-eval-string", vals, env) {
+        Ok(e) => match &*e {
+            Expr::Str(s) => s.clone(),
+            _ => return EvalResult::Err(format!("read-eval-string: expected a string, got {:?}", e)),
+        },
+        Err(err) => return EvalResult::Err(err),
+    };
+    let tokens = match lex(&source) {
+        Ok(tokens) => tokens,
+        Err(e) => return EvalResult::Err(format!("read-eval-string: Lex error: {:?}", e)),
+    };
+    match parse(&tokens) {
+        Ok(expr) => eval(expr, env),
+        Err(e) => EvalResult::Err(format!("read-eval-string: Parse error: {:?}", e)),
+    }
+}
+
+/// `(curry f a)` returns a function that calls `f` with `a` followed by its own arguments.
+fn curry(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("curry must follow the pattern (curry <function> <argument>)".into());
+    }
+    match eval_args(vals, env) {
+        Ok(args) if is_callable(&args[0], env) => EvalResult::Expr(Expr::partial(args[0].clone(), &args[1..])),
+        Ok(args) => EvalResult::Err(format!("curry: expected a function, got {:?}", args[0])),
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `(compose f g ...)` returns a one-argument function applying the functions right to left,
+/// so `((compose f g) x)` is `(f (g x))`. The result is a lambda whose body calls each
+/// quoted function value as a computed head, under a parameter name user code is unlikely to use.
+fn compose(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() < 2 {
+        return EvalResult::Err("compose must follow the pattern (compose <function> <function> ..)".into());
+    }
+    let fs = match eval_args(vals, env) {
+        Ok(fs) => fs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    if let Some(f) = fs.iter().find(|f| !is_callable(f, env)) {
+        return EvalResult::Err(format!("compose: expected a function, got {:?}", f));
+    }
+    let param = "#x".to_string();
+    let body = fs.iter().rev().fold(Expr::symbol(&param), |inner, f| {
+        Expr::list(&[Expr::list(&[Expr::symbol("quote"), f.clone()]), inner])
+    });
+    EvalResult::Expr(Expr::lambda(&[param], body))
+}
+
+/// `(repeat n x)` returns a list holding `n` copies of the value of `x`.
+fn repeat(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("repeat must follow the pattern (repeat <n> <value>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match as_integer("repeat", &args[0]) {
+        Ok(n) if n < 0 => EvalResult::Err(format!("repeat: count must not be negative, got {}", n)),
+        Ok(n) if n as u64 > MAX_LIST_LENGTH as u64 => {
+            EvalResult::Err(format!("repeat: count must be at most {}, got {}", MAX_LIST_LENGTH, n))
+        },
+        Ok(n) => EvalResult::Expr(Expr::list(&vec![args[1].clone(); n as usize])),
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `(string->number-or s default)` parses `s` the way the reader parses number literals,
+/// evaluating and returning `default` only when that fails.
+fn string_to_number_or(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("string->number-or must follow the pattern (string->number-or <string> <default>)".into());
+    }
+    let s = match eval_args(&vals[..1], env) {
+        Ok(args) => match &*args[0] {
+            Expr::Str(s) => s.clone(),
+            _ => return EvalResult::Err(format!("string->number-or: expected a string, got {:?}", args[0])),
+        },
+        Err(err) => return EvalResult::Err(err),
+    };
+    match s.trim().parse::<f64>() {
+        Ok(n) => EvalResult::Expr(Expr::fnum(n)),
+        Err(_) => eval(vals[1].clone(), env),
+    }
+}
+
+/// `(bound? (quote name))` reports whether `name` has a binding, without evaluating it.
+/// The `OPERATORS` count as bound, since they read as function values either way.
+fn is_bound(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match eval_unary("bound?", vals, env) {
+        Ok(e) => match &*e {
+            Expr::Symbol(s) => {
+                let bound = env.contains_key(s) || OPERATORS.contains(&s.as_str());
+                EvalResult::Expr(Expr::symbol(if bound { "True" } else { "False" }))
+            },
+            _ => EvalResult::Err(format!("bound?: expected a symbol, got {:?}", e)),
+        },
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `None` when a step overflows `i64`, as `i64::MIN` and `-1` do.
+fn gcd_of(a: i64, b: i64) -> Option<i64> {
+    if b == 0 { a.checked_abs() } else { gcd_of(b, a.checked_rem(b)?) }
+}
+
+fn lcm_of(a: i64, b: i64) -> Option<i64> {
+    if a == 0 || b == 0 { Some(0) } else { (a / gcd_of(a, b)?).checked_mul(b)?.checked_abs() }
+}
+
+/// Shared by `gcd` and `lcm`: folds two or more integer arguments pairwise with `combine`,
+/// which gives `None` on overflow.
+fn fold_integers(op: &str, vals: &[Rc<Expr>], env: &mut Environment, combine: fn(i64, i64) -> Option<i64>) -> EvalResult {
+    if vals.len() < 2 {
+        return EvalResult::Err(format!("{} must follow the pattern ({} <n> <n> ..)", op, op));
+    }
+    eval_args(vals, env)
+        .and_then(|args| args.iter().map(|x| as_integer(op, x)).collect::<Result<Vec<i64>, String>>())
+        .map_or_else(EvalResult::Err, |ns| {
+            match ns[1..].iter().try_fold(ns[0], |acc, &n| combine(acc, n)) {
+                Some(folded) => EvalResult::Expr(Expr::fnum(folded as f64)),
+                None => EvalResult::Err(format!("{}: result overflows a 64-bit integer", op)),
+            }
+        })
+}
+
+/// `(divides? d n)` reports whether the integer `n` is a multiple of the integer `d`.
+fn divides(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("divides? must follow the pattern (divides? <divisor> <n>)".into());
+    }
+    let (d, n) = match eval_args(vals, env).and_then(|args| Ok((as_integer("divides?", &args[0])?, as_integer("divides?", &args[1])?))) {
+        Ok(dn) => dn,
+        Err(err) => return EvalResult::Err(err),
+    };
+    if d == 0 {
+        return EvalResult::Err("divides?: divisor must not be zero".into());
+    }
+    // The remainder only overflows for `i64::MIN` and `-1`, where it is 0.
+    let divisible = n.checked_rem(d).unwrap_or(0) == 0;
+    EvalResult::Expr(Expr::symbol(if divisible { "True" } else { "False" }))
+}
+
+/// Checks that `e` is a non-negative integer, as the argument of `factorial` and the exponent of `expt` must be.
+fn non_negative_integer(op: &str, e: &Expr) -> Result<u64, String> {
+    match as_integer(op, e) {
+        Ok(n) if n < 0 => Err(format!("{}: expected a non-negative integer, got {}", op, n)),
+        Ok(n) => Ok(n as u64),
+        Err(err) => Err(err),
+    }
+}
+
+/// `(factorial n)` multiplies 1 through `n` in a loop, so large `n` cannot exhaust the stack.
+fn factorial(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let n = match eval_unary("factorial", vals, env).and_then(|e| non_negative_integer("factorial", &e)) {
+        Ok(n) => n,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let mut product = 1.0;
+    for i in 2..=n {
+        product *= i as f64;
+        if product.is_infinite() {
+            return EvalResult::Err(format!("factorial: result of (factorial {}) overflows", n));
+        }
+    }
+    EvalResult::Expr(Expr::fnum(product))
+}
+
+/// `(expt base n)` raises `base` to the non-negative integer power `n` by repeated squaring.
+fn expt(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("expt must follow the pattern (expt <base> <exponent>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let base = match &*args[0] {
+        Expr::FNum(b) => *b,
+        _ => return EvalResult::Err(format!("expt: expected a number, got {:?}", args[0])),
+    };
+    let exponent = match non_negative_integer("expt", &args[1]) {
+        Ok(n) => n,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let (mut result, mut square, mut n) = (1.0, base, exponent);
+    while n > 0 {
+        if n % 2 == 1 {
+            result *= square;
+        }
+        square *= square;
+        n /= 2;
+    }
+    if result.is_infinite() && base.is_finite() {
+        return EvalResult::Err(format!("expt: result of (expt {} {}) overflows", format_number(base), exponent));
+    }
+    EvalResult::Expr(Expr::fnum(result))
+}
+
+/// `(values-of (quote a) (quote b) ..)` lists the values bound to each name. Functions give
+/// their function value. Unbound names give `()`, or an error with `strict_values_of` set.
+fn values_of(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let names = match eval_args(vals, env) {
+        Ok(names) => names,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let mut values = Vec::new();
+    for name in names {
+        let s = match &*name {
+            Expr::Symbol(s) => s,
+            _ => return EvalResult::Err(format!("values-of: expected a symbol, got {:?}", name)),
+        };
+        match env.lookup(s) {
+            Some((params, body)) if !params.is_empty() => values.push(Expr::lambda(&params, body)),
+            Some(_) => match eval(name.clone(), env) {
+                EvalResult::Expr(value) => values.push(value),
+                EvalResult::Unit => values.push(Expr::list(&[])),
+                err => return err,
+            },
+            None if env.strict_values_of => return EvalResult::Err(format!("values-of: unbound variable {}", s)),
+            None => values.push(Expr::list(&[])),
+        }
+    }
+    EvalResult::Expr(Expr::list(&values))
+}
+
+/// `(print-each list)` prints every element of `list` on its own line.
+fn print_each(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let xs = match eval_unary("print-each", vals, env).and_then(|lst| expect_list(&lst, "print-each")) {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    for x in xs {
+        let line = gen_print_output(x, env);
+        if let Err(err) = env.write_output(&format!("{}\n", line)) {
+            return EvalResult::Err(err);
+        }
+    }
+    EvalResult::Unit
+}
+
+fn paths_into(xs: &[Rc<Expr>], prefix: &mut Vec<Rc<Expr>>, out: &mut Vec<Rc<Expr>>) {
+    for (i, x) in xs.iter().enumerate() {
+        prefix.push(Expr::fnum(i as f64));
+        match x.items() {
+            Some(ys) if !ys.is_empty() => paths_into(&ys, prefix, out),
+            _ => out.push(Expr::list(&[Expr::list(prefix), x.clone()])),
+        }
+        prefix.pop();
+    }
+}
+
+/// `(paths list)` pairs every leaf of a nested list with the indices leading to it:
+/// `((index-path leaf) ..)`. A nested empty list counts as a leaf.
+fn paths(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("paths", vals, env)
+        .and_then(|lst| expect_list(&lst, "paths"))
+        .map_or_else(EvalResult::Err, |xs| {
+            let mut out = Vec::new();
+            paths_into(&xs, &mut Vec::new(), &mut out);
+            EvalResult::Expr(Expr::list(&out))
+        })
+}
+
+fn nesting_depth(e: &Expr) -> usize {
+    e.items().map_or(0, |xs| 1 + xs.iter().map(|x| nesting_depth(x)).max().unwrap_or(0))
+}
+
+/// `(list-depth x)` is the deepest nesting of lists in `x`: 0 for an atom, 1 for a flat list.
+fn list_depth(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("list-depth", vals, env).map_or_else(
+        EvalResult::Err,
+        |e| EvalResult::Expr(Expr::fnum(nesting_depth(&e) as f64)),
+    )
+}
+
+/// `(car-or list default)` is the first element of `list`, or the value of `default` when
+/// `list` is empty. `default` is only evaluated when it is needed.
+fn car_or(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("car-or must follow the pattern (car-or <list> <default>)".into());
+    }
+    let lst = match eval_args(&vals[..1], env) {
+        Ok(args) => args[0].clone(),
+        Err(err) => return EvalResult::Err(err),
+    };
+    match lst.car() {
+        Some(head) => EvalResult::Expr(head),
+        None => expect_list(&lst, "car-or").map_or_else(EvalResult::Err, |_| eval(vals[1].clone(), env)),
+    }
+}
+
+/// `(type-of x)` names the kind of value `x` evaluates to, as a symbol.
+fn type_of(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("type-of", vals, env).map_or_else(EvalResult::Err, |e| {
+        let name = match &*e {
+            Expr::Symbol(_) => "symbol",
+            Expr::FNum(_) => "number",
+            Expr::Str(_) => "string",
+            Expr::Char(_) => "char",
+            Expr::List(_) | Expr::Pair(..) => "list",
+            Expr::Lambda(..) | Expr::Partial(..) => "function",
+            Expr::Promise(..) => "promise",
+            Expr::Map(_) => "map",
+            Expr::Values(_) => "values",
+        };
+        EvalResult::Expr(Expr::symbol(name))
+    })
+}
+
+/// `(swap i j list)` returns a copy of `list` with the elements at `i` and `j` exchanged.
+fn swap(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("swap must follow the pattern (swap <index> <index> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[2], "swap")
+        .and_then(|xs| {
+            let i = list_index("swap", &args[0], xs.len(), xs.len())?;
+            let j = list_index("swap", &args[1], xs.len(), xs.len())?;
+            Ok((i, j, xs))
+        })
+        .map_or_else(EvalResult::Err, |(i, j, mut xs)| {
+            xs.swap(i, j);
+            EvalResult::Expr(Expr::list(&xs))
+        })
 }
 
-impl Environment {
-    pub fn empty() -> Environment {
-        Environment {
-            contexts: Vec::new(),
+/// `(fold-until f init list stop?)` folds like `reduce`, but returns the accumulator as soon
+/// as `stop?` of it is truthy, without calling `f` on the remaining elements.
+fn fold_until(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 4 {
+        return EvalResult::Err("fold-until must follow the pattern (fold-until <function> <initial> <list> <predicate>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let xs = match expect_list(&args[2], "fold-until") {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let mut acc = args[1].clone();
+    for x in xs {
+        match apply_for_value("fold-until", args[3].clone(), std::slice::from_ref(&acc), env) {
+            Ok(stop) if is_truthy(&stop) => break,
+            Ok(_) => {},
+            Err(err) => return EvalResult::Err(err),
         }
+        acc = match apply_for_value("fold-until", args[0].clone(), &[acc, x], env) {
+            Ok(next) => next,
+            Err(err) => return EvalResult::Err(err),
+        };
     }
+    EvalResult::Expr(acc)
+}
 
-    /// Helper function for tests
-    pub fn from_vars(vars: &[(&str, Rc<Expr>)]) -> Environment {
-        let mut env = Environment::empty();
-        env.push_context();
-        vars.iter().for_each(|(name, expr)| {
-            let _ = env.add_var(name, expr.clone());
-        });
-        env
+/// `(interleave a b)` alternates the elements of `a` and `b`, starting with `a`. It stops
+/// when the shorter list runs out, so extra elements of the longer one are dropped.
+fn interleave(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("interleave must follow the pattern (interleave <list> <list>)".into());
     }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[0], "interleave")
+        .and_then(|xs| expect_list(&args[1], "interleave").map(|ys| (xs, ys)))
+        .map_or_else(EvalResult::Err, |(xs, ys)| {
+            let mixed: Vec<Rc<Expr>> = xs.into_iter().zip(ys).flat_map(|(x, y)| [x, y]).collect();
+            EvalResult::Expr(Expr::list(&mixed))
+        })
+}
 
-    pub fn default() -> Environment {
-        let defaults: HashMap<String, (Vec<String>, Rc<Expr>)> = [
-            ("False".into(), (Vec::new(), Expr::list(&[]))), ("True".into(), (Vec::new(), Expr::list(&[Expr::fnum(1.0)]))),
-        ].iter().cloned().collect();
-        Environment{
-            contexts: vec![defaults],
+/// `(lookup (quote name))` shows how `name` is stored: `(params body)`, with the parameter
+/// names as symbols and the body unevaluated, or `()` when `name` is unbound.
+fn lookup_binding(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match eval_unary("lookup", vals, env) {
+        Ok(e) => match &*e {
+            Expr::Symbol(s) => EvalResult::Expr(env.lookup(s).map_or_else(
+                || Expr::list(&[]),
+                |(params, body)| {
+                    let params: Vec<Rc<Expr>> = params.iter().map(|p| Expr::symbol(p)).collect();
+                    Expr::list(&[Expr::list(&params), body])
+                },
+            )),
+            _ => EvalResult::Err(format!("lookup: expected a symbol, got {:?}", e)),
+        },
+        Err(err) => EvalResult::Err(err),
+    }
+}
+
+/// `(load "path")` reads a file of forms and evaluates them in order in the current
+/// environment, returning the value of the last one. Errors name the file.
+fn load(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let path = match eval_unary("load", vals, env) {
+        Ok(e) => match &*e {
+            Expr::Str(s) => s.clone(),
+            _ => return EvalResult::Err(format!("load: expected a path string, got {:?}", e)),
+        },
+        Err(err) => return EvalResult::Err(err),
+    };
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) => return EvalResult::Err(format!("load {}: {}", path, err)),
+    };
+    let forms = match lex(&source) {
+        Ok(tokens) => match parse_program(&tokens) {
+            Ok(forms) => forms,
+            Err(e) => return EvalResult::Err(format!("load {}: Parse error: {:?}", path, e)),
+        },
+        Err(e) => return EvalResult::Err(format!("load {}: Lex error: {:?}", path, e)),
+    };
+    let mut result = EvalResult::Unit;
+    for form in forms {
+        result = match eval(form, env) {
+            EvalResult::Err(err) => return EvalResult::Err(format!("load {}: {}", path, err)),
+            r => r,
+        };
+    }
+    result
+}
+
+/// `(frequencies list)` counts the elements of `list` that are `=` to each other, as
+/// `(element count)` pairs in order of first appearance.
+fn frequencies(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    eval_unary("frequencies", vals, env)
+        .and_then(|lst| expect_list(&lst, "frequencies"))
+        .map_or_else(EvalResult::Err, |xs| {
+            let mut tally: Vec<(Rc<Expr>, usize)> = Vec::new();
+            for x in xs {
+                match tally.iter_mut().find(|(seen, _)| *seen == x) {
+                    Some(entry) => entry.1 += 1,
+                    None => tally.push((x, 1)),
+                }
+            }
+            let pairs: Vec<Rc<Expr>> = tally.into_iter()
+                .map(|(x, n)| Expr::list(&[x, Expr::fnum(n as f64)]))
+                .collect();
+            EvalResult::Expr(Expr::list(&pairs))
+        })
+}
+
+/// `(scan f init list)` folds like `reduce` but returns every accumulator value, starting with `init`.
+fn scan(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 3 {
+        return EvalResult::Err("scan must follow the pattern (scan <function> <initial> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let xs = match expect_list(&args[2], "scan") {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let mut steps = vec![args[1].clone()];
+    for x in xs {
+        let acc = steps[steps.len() - 1].clone();
+        match apply_for_value("scan", args[0].clone(), &[acc, x], env) {
+            Ok(next) => steps.push(next),
+            Err(err) => return EvalResult::Err(err),
         }
+    }
+    EvalResult::Expr(Expr::list(&steps))
+}
 
+/// `(unit? x)` reports whether `x` evaluates to Unit, the "no value" of `print` and of the
+/// `unit` literal, as opposed to the empty list `()`.
+fn is_unit(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err(format!("unit? takes exactly one argument, got {}", vals.len()));
+    }
+    match eval(vals[0].clone(), env) {
+        EvalResult::Unit => EvalResult::Expr(Expr::symbol("True")),
+        EvalResult::Expr(_) => EvalResult::Expr(Expr::symbol("False")),
+        err => err,
+    }
+}
 
+/// `(transpose rows)` swaps the rows and columns of a list of equal-length lists.
+fn transpose(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let rows = eval_unary("transpose", vals, env)
+        .and_then(|lst| expect_list(&lst, "transpose"))
+        .and_then(|rows| rows.iter().map(|row| expect_list(row, "transpose")).collect::<Result<Vec<_>, String>>());
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let width = rows.first().map_or(0, |row| row.len());
+    if let Some(i) = rows.iter().position(|row| row.len() != width) {
+        return EvalResult::Err(format!("transpose: row {} has length {}, expected {}", i, rows[i].len(), width));
     }
+    let columns: Vec<Rc<Expr>> = (0..width)
+        .map(|j| Expr::list(&rows.iter().map(|row| row[j].clone()).collect::<Vec<_>>()))
+        .collect();
+    EvalResult::Expr(Expr::list(&columns))
+}
 
-    /// Looks up the given symbol in the Environment.
-    pub fn lookup(&self, symbol: &str) -> Option<(Vec<String>, Rc<Expr>)> {
-        self.contexts.iter().rev()
-            .find(|cntxt| cntxt.contains_key(symbol))
-            .map(|cntxt| cntxt.get(symbol))
-            .flatten().cloned()
+/// `(index-of x list)` is the index of the first element of `list` that is `=` to `x`, or -1.
+fn index_of(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("index-of must follow the pattern (index-of <value> <list>)".into());
     }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "index-of").map_or_else(
+        EvalResult::Err,
+        |xs| EvalResult::Expr(Expr::fnum(xs.iter().position(|x| args[0].eq(x)).map_or(-1.0, |i| i as f64))),
+    )
+}
 
-    /// Checks whether the given symbol exists in the Environment.
-    pub fn contains_key(&self, symbol: &str) -> bool {
-        self.contexts.iter().rev()
-            .find(|cntxt| cntxt.contains_key(symbol))
-            .is_some()
+/// `(map-pairs f list)` calls `f` on each pair of adjacent elements, giving a list one shorter than `list`.
+fn map_pairs(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("map-pairs must follow the pattern (map-pairs <function> <list>)".into());
     }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "map-pairs")
+        .and_then(|xs| xs.windows(2)
+            .map(|pair| apply_for_value("map-pairs", args[0].clone(), pair, env))
+            .collect::<Result<Vec<_>, String>>())
+        .map_or_else(EvalResult::Err, |ys| EvalResult::Expr(Expr::list(&ys)))
+}
 
-    /// Pushes a new context on the `contexts` stack.
-    pub fn push_context(&mut self) {
-        self.contexts.push(HashMap::new());
+/// `(map-indexed f list)` calls `f` with the index and the element at each position of `list`.
+fn map_indexed(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("map-indexed must follow the pattern (map-indexed <function> <list>)".into());
     }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "map-indexed")
+        .and_then(|xs| xs.into_iter().enumerate()
+            .map(|(i, x)| apply_for_value("map-indexed", args[0].clone(), &[Expr::fnum(i as f64), x], env))
+            .collect::<Result<Vec<_>, String>>())
+        .map_or_else(EvalResult::Err, |ys| EvalResult::Expr(Expr::list(&ys)))
+}
 
-    /// Pops the last context from the `contexts` stack.
-    pub fn pop_context(&mut self) {
-        self.contexts.pop();
+fn function_value(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("function takes exactly one argument: (function fn-name)".into());
     }
+    match &*vals[0] {
+        Expr::Symbol(s) => match env.lookup(s) {
+            Some((params, body)) if !params.is_empty() => EvalResult::Expr(Expr::lambda(&params, body)),
+            Some(_) => EvalResult::Err(format!("{} is not a function.", s)),
+            None => EvalResult::Err(format!("Undefined function: {}", s)),
+        },
+        _ => EvalResult::Err(format!("function expects a function name, got {:?}", vals[0])),
+    }
+}
 
-    /// Adds a variable definition to the Environment
-    pub fn add_var(&mut self, var: &str, val: Rc<Expr>) -> Result<(), String> {
-        self.contexts.last_mut()
-            .map_or_else(
-                || Err("Environment has no context to add to.".into()),
-                |cntxt| { cntxt.insert(var.to_string(), (Vec::new(), val.clone())); Ok(()) },
-            )
+fn map_fn(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("map must follow the pattern (map <function> <list>)".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    match expect_list(&args[1], "map") {
+        Ok(xs) => {
+            let mapped: Result<Vec<Rc<Expr>>, EvalResult> = xs.iter()
+                .map(|x| match apply_fn(args[0].clone(), std::slice::from_ref(x), env) {
+                    EvalResult::Expr(e) => Ok(e),
+                    EvalResult::Unit => Err(EvalResult::Err("map function returned Unit.".into())),
+                    err => Err(err),
+                }).collect();
+            mapped.map_or_else(|err| err, |xs| EvalResult::Expr(Expr::list(&xs)))
+        },
+        Err(err) => EvalResult::Err(err),
     }
+}
 
-    /// Adds a function definition to the Environment
-    pub fn add_fn(&mut self, name: &str, params: &[String], body: Rc<Expr>) -> Result<(), String> {
-        self.contexts.last_mut().map_or(
-            Err("Environment does not have a context to add to.".into()),
-            |cntxt| {
-                let param_names: Vec<String> = params.iter().map(|s| s.to_string()).collect();
-                cntxt.insert(name.into(), (param_names, body.clone()));
-                Ok(())
-            },
-        )
+fn apply_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("apply must follow the pattern (apply <function> <list>)".into());
     }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    expect_list(&args[1], "apply").map_or_else(
+        EvalResult::Err,
+        |xs| apply_fn(args[0].clone(), &xs, env),
+    )
+}
 
-    pub fn num_contexts(&self) -> usize {
-        self.contexts.len()
+/// Formats a number for `print`. `NaN`, `inf` and `-inf` read back as the same
+/// values, and `-0` prints as `0` since the two compare equal.
+fn format_number(n: f64) -> String {
+    match n {
+        _ if n.is_nan() => "NaN".into(),
+        _ if n == 0.0 => "0".into(),
+        _ => format!("{}", n),
     }
 }
 
-fn eval_symbol(expr: Rc<Expr>, sym: &str, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    env.lookup(sym)
-        .map_or_else(
-            || EvalResult::Expr(expr),
-            |(param_names, expression)| {
-                if param_names.is_empty(){
-                    eval(expression.clone(), env)
-                } else {
-                    if args.len() != param_names.len() {
-                        return EvalResult::Err(format!("provided {} arguments but expected {}", args.len(), param_names.len()));
-                    }
-                    let mapped_args: Result<Vec<(String, Rc<Expr>)>, String> = args.iter().zip(param_names)
-                        .map(|(expr, name)| match eval(expr.clone(), env) {
-                           EvalResult::Expr(e) => Ok((name.into(), e.clone())),
-                           EvalResult::Err(err) => Err(err),
-                           _ => Err("Cannot pass Unit as an argument to a function.".into()),
-                        }).collect();
-
-                    env.push_context();
-                    let result = mapped_args.map_or_else(
-                        |e| EvalResult::Err(e),
-                        |argum| {
-                            argum.iter().for_each(|(name, expr)| { let _ = env.add_var(name, expr.clone()); 
-                            }); 
-                            eval(expression.clone(), env)
-                        },
-                    );
-                    env.pop_context();
-                    result
-                }    
+/// Line width `pprint` uses when none is given.
+pub const DEFAULT_PRETTY_WIDTH: usize = 80;
+
+/// Renders `expr` without consulting an environment: lists that fit in `width`
+/// stay on one line, longer ones put each element on its own indented line.
+pub fn pretty(expr: &Expr, width: usize) -> String {
+    pretty_at(expr, 0, width)
+}
+
+fn flat(expr: &Expr) -> String {
+    render(expr, None, &mut Vec::new())
+}
+
+/// Renders `expr` on one line. With an environment, symbols print as the values they are
+/// bound to and named functions by name and parameters; without one they print as written.
+/// `resolving` holds the variables whose values are being rendered, to catch cycles.
+fn render(expr: &Expr, env: Option<&Environment>, resolving: &mut Vec<String>) -> String {
+    match expr {
+        Expr::Symbol(s) => match env.and_then(|env| env.lookup(s)) {
+            None => s.clone(),
+            Some((params, _)) if params.is_empty() && resolving.contains(s) => {
+                format!("#{}#", resolving.iter().position(|r| r == s).unwrap_or_default())
             },
-        )
+            Some((params, value)) if params.is_empty() => {
+                resolving.push(s.clone());
+                let out = render(&value, env, resolving);
+                resolving.pop();
+                out
+            },
+            Some((params, _)) => format!("<func-object: {} ({})>", s, params.join(" ")),
+        },
+        Expr::Str(s) => s.clone(),
+        Expr::FNum(n) => format_number(*n),
+        Expr::Char(c) => c.to_string(),
+        Expr::Lambda(params, ..) => format!("<func-object: lambda ({})>", params.join(" ")),
+        Expr::Promise(..) => "<promise>".into(),
+        Expr::Partial(f, args) => {
+            let given: Vec<String> = args.iter().map(|x| render(x, env, resolving)).collect();
+            format!("<partial: {} {}>", render(f, env, resolving), given.join(" "))
+        },
+        Expr::Map(entries) => {
+            let es: Vec<String> = entries.iter().map(|(k, v)| format!("{} {}", render(k, env, resolving), render(v, env, resolving))).collect();
+            format!("{{{}}}", es.join(", "))
+        },
+        Expr::List(_) | Expr::Pair(..) => {
+            let items: Vec<String> = expr.items().unwrap_or_default().iter().map(|x| render(x, env, resolving)).collect();
+            format!("({})", items.join(" "))
+        },
+        Expr::Values(xs) => {
+            let items: Vec<String> = xs.iter().map(|x| render(x, env, resolving)).collect();
+            format!("<values: {}>", items.join(" "))
+        },
+    }
+}
+
+fn pretty_at(expr: &Expr, indent: usize, width: usize) -> String {
+    let one_line = flat(expr);
+    match expr.items() {
+        Some(items) if !items.is_empty() && indent + one_line.len() > width => {
+            let lines: Vec<String> = items.iter().map(|x| pretty_at(x, indent + 1, width)).collect();
+            format!("({})", lines.join(&format!("\n{}", " ".repeat(indent + 1))))
+        },
+        _ => one_line,
+    }
+}
+
+/// `(pprint expr [width])` prints the value of `expr` with `pretty`.
+fn pprint(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.is_empty() || vals.len() > 2 {
+        return EvalResult::Err("pprint must follow the pattern (pprint <expr> [width])".into());
+    }
+    let args = match eval_args(vals, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let width = match args.get(1).map(|w| as_integer("pprint", w)) {
+        None => DEFAULT_PRETTY_WIDTH,
+        Some(Ok(w)) if w > 0 => w as usize,
+        Some(Ok(w)) => return EvalResult::Err(format!("pprint: width must be positive, got {}", w)),
+        Some(Err(err)) => return EvalResult::Err(err),
+    };
+    env.write_output(&format!("{}\n", pretty(&args[0], width)))
+        .map_or_else(EvalResult::Err, |_| EvalResult::Unit)
 }
 
 /// Generates the output printed to standard out when the user calls print.
+///
+/// Symbols print as the values they are bound to, so a symbol bound (directly or through
+/// other variables) to itself would never finish printing. Such a symbol prints as `#n#`,
+/// where `n` counts the variables already being printed on the way to it, starting at 0.
 pub fn gen_print_output(expr: Rc<Expr>, env: &mut Environment) -> String {
-    match &*expr {
-        Expr::Symbol(s) => {
-            match env.lookup(&s) {
-                None => s.into(),
-                Some((params, e)) if params.len() == 0 => gen_print_output(e, env),
-                _ => format!("<func-object: {}>", s.to_string()),
-            }
+    render(&expr, Some(env), &mut Vec::new())
+}
+
+/// A definition named after a special form can never be called, because `eval`
+/// dispatches the form first; a variable of that name can still be read as a value.
+/// Records a warning, or fails in strict mode.
+fn check_reserved(name: &str, is_function: bool, env: &mut Environment) -> Result<(), String> {
+    if !SPECIAL_FORMS.contains(&name) {
+        return Ok(());
+    }
+    if env.strict {
+        return Err(format!("cannot define {}: it is a special form", name));
+    }
+    env.warnings.push(if is_function {
+        format!("{} is a special form, so this definition is never called", name)
+    } else {
+        format!("{} is a special form, so this variable can be read but not called", name)
+    });
+    Ok(())
+}
+
+/// `(let name ((param init) ..) body)` defines `name` as a function of the params, visible
+/// only inside `body`, and calls it with the inits. Calling `name` from `body` loops; calls
+/// in tail position, including through the branches of `if`, do not grow the stack.
+fn named_let(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let name = match &*vals[0] {
+        Expr::Symbol(s) => s.clone(),
+        e => return EvalResult::Err(format!("named let: loop name must be a symbol, got {:?}", e)),
+    };
+    if let Err(err) = check_reserved(&name, true, env) {
+        return EvalResult::Err(err);
+    }
+    let bindings = match vals[1].items() {
+        Some(bindings) if !bindings.is_empty() => bindings,
+        _ => return EvalResult::Err(format!("named let: expected a non-empty list of (name value) bindings, got {:?}", vals[1])),
+    };
+    let mut params = Vec::new();
+    let mut inits = Vec::new();
+    for binding in &bindings {
+        match binding.items().as_deref() {
+            Some([param, init]) => match &**param {
+                Expr::Symbol(p) => {
+                    params.push(p.clone());
+                    inits.push(init.clone());
+                },
+                _ => return EvalResult::Err(format!("named let: parameter name must be a symbol, got {:?}", param)),
+            },
+            _ => return EvalResult::Err(format!("named let: each binding must look like (name value), got {:?}", binding)),
         }
-        Expr::FNum(n) => format!("{}", n),
-        Expr::List(vals) => {
-            let vals_out: Vec<String> = vals.iter().cloned()
-                .map(|x| gen_print_output(x, env)).collect();
-            format!("({})", vals_out.join(" "))
-                
+    }
+    let args = match eval_args(&inits, env) {
+        Ok(args) => args,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let body = vals[2].clone();
+    env.push_context();
+    let result = match env.add_fn(&name, &params, body.clone()) {
+        Ok(()) => in_call_to(&name, run_loop(&params, &body, args, env)),
+        Err(err) => EvalResult::Err(err),
+    };
+    env.pop_context();
+    result
+}
+
+/// What evaluating the body of a named let produced: its value, or the arguments of a call
+/// back to the loop in tail position.
+enum Tail {
+    Done(EvalResult),
+    Loop(Vec<Rc<Expr>>),
+}
+
+/// Calls the function of a named let, running each tail call back to it as another
+/// iteration rather than a nested call.
+fn run_loop(params: &[String], body: &Rc<Expr>, mut args: Vec<Rc<Expr>>, env: &mut Environment) -> EvalResult {
+    loop {
+        if args.len() != params.len() {
+            return EvalResult::Err(format!("provided {} arguments but expected {}", args.len(), params.len()));
+        }
+        env.push_context();
+        params.iter().zip(&args).for_each(|(name, expr)| {
+            let _ = env.add_var(name, expr.clone());
+        });
+        let step = eval_tail(body.clone(), body, env);
+        env.pop_context();
+        match step {
+            Tail::Loop(next) => args = next,
+            Tail::Done(result) => return result,
         }
     }
 }
 
-fn add_var_to_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.len() != 2 {
-        return EvalResult::Err("Invalid variable definition. Should look like (let someVar someExpr)".into(),
+/// Evaluates `expr` in tail position of the loop whose body is `body`. A call to a function
+/// with that body, reached directly or through `if` branches, is returned unevaluated.
+fn eval_tail(mut expr: Rc<Expr>, body: &Rc<Expr>, env: &mut Environment) -> Tail {
+    loop {
+        let call = match &*expr {
+            Expr::List(xs) if !xs.is_empty() => xs.clone(),
+            _ => return Tail::Done(eval(expr, env)),
+        };
+        match &*call[0] {
+            Expr::Symbol(s) if s == "if" && !env.macros.contains_key(s) => match if_branch(&call[1..], env) {
+                Ok(Some(branch)) => expr = branch,
+                Ok(None) => return Tail::Done(EvalResult::Unit),
+                Err(err) => return Tail::Done(err),
+            },
+            Expr::Symbol(s) if env.lookup(s).is_some_and(|(params, f)| !params.is_empty() && Rc::ptr_eq(&f, body)) => {
+                return eval_args(&call[1..], env).map_or_else(|err| Tail::Done(EvalResult::Err(err)), Tail::Loop);
+            },
+            _ => return Tail::Done(eval(expr, env)),
+        }
+    }
+}
 
-        );
+/// Shared by `let` and `var`, which also makes the binding mutable under `immutable_lets`.
+fn add_var_to_env(op: &str, vals: &[Rc<Expr>], env: &mut Environment, mutable: bool) -> EvalResult {
+    if vals.len() == 3 && !mutable {
+        return named_let(vals, env);
+    }
+    if vals.len() != 2 {
+        return EvalResult::Err(format!("Invalid variable definition. Should look like ({} someVar someExpr)", op));
+    }
+    if let Expr::Symbol(s) = &*vals[0] {
+        if let Err(err) = check_reserved(s, false, env) {
+            return EvalResult::Err(err);
+        }
     }
     match( &*vals[0], &vals[1]) {
         (Expr::Symbol(s), e) => match eval(e.clone(), env) {
-            EvalResult::Expr(e) => env.add_var(s, e)
+            EvalResult::Expr(e) => {
+                let added = if mutable { env.add_mutable_var(s, e.clone()) } else { env.add_var(s, e.clone()) };
+                added.map_or_else(EvalResult::Err, |_| EvalResult::Expr(e))
+            },
+            EvalResult::Unit => EvalResult::Err("cannot assign Unit to a variable.".into()),
+            err => err,
+        },
+        (name, _) => EvalResult::Err(format!("variable name must be a symbol, got {:?}", name)),
+    }
+}
+
+fn set_var_in_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("Invalid assignment. Should look like (set! someVar someExpr)".into());
+    }
+    match (&*vals[0], &vals[1]) {
+        (Expr::Symbol(s), e) => match eval(e.clone(), env) {
+            EvalResult::Expr(e) => env.set_var(s, e.clone())
                 .map_or_else(
-                    |s| EvalResult::Err(s), 
-                    |_| EvalResult::Unit,
+                    EvalResult::Err,
+                    |_| EvalResult::Expr(e),
                 ),
             EvalResult::Unit => EvalResult::Err("cannot assign Unit to a variable.".into()),
             err => err,
         },
-        _ => EvalResult::Err("Second element of variable def must be a symbol and third must be expression.".into(),
-        ),
+        (name, _) => EvalResult::Err(format!("variable name must be a symbol, got {:?}", name)),
+    }
+}
+
+/// `(undef name)` removes the binding of `name`, which is not evaluated.
+fn undef(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match vals {
+        [name] => match &**name {
+            Expr::Symbol(s) => env.remove_var(s).map_or_else(EvalResult::Err, |_| EvalResult::Unit),
+            _ => EvalResult::Err(format!("variable name must be a symbol, got {:?}", name)),
+        },
+        _ => EvalResult::Err("undef must follow the pattern (undef <name>)".into()),
     }
 }
 
 fn add_fn_to_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
     if vals.len() != 3 {
-        EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>)".into());
+        return EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>)".into());
     }
     let fn_name = &*vals[0];
     let p_names = &*vals[1];
     let body = &vals[2];
-    match(&*fn_name, p_names, body){
+    match(fn_name, p_names, body){
         (Expr::Symbol(fn_name), Expr::List(params), body) => {
-            let ps: Result<Vec<String>, String> = params.iter().cloned().map(|e| {
-                if let Expr::Symbol(n) = &*e {
+            if let Err(err) = check_reserved(fn_name, true, env) {
+                return EvalResult::Err(err);
+            }
+            let ps: Result<Vec<String>, String> = params.iter().map(|e| {
+                if let Expr::Symbol(n) = &**e {
                     Ok(n.into())
                 } else {
-                    Err("Function parameters must be symbols.".into())
+                    Err(format!("function parameter must be a symbol, got {:?}", e))
                 }
             }).collect();
             ps.map_or_else(
-                |err| EvalResult::Err(err),
+                EvalResult::Err,
                 |xs| env.add_fn(fn_name, xs.as_slice(), body.clone()).map_or_else(
-                    |err| EvalResult::Err(err),
+                    EvalResult::Err,
                     |_| EvalResult::Unit,
                 )
             )
         },
-        _ => EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>)".into()),
+        (Expr::Symbol(_), _, _) => EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>)".into()),
+        (name, _, _) => EvalResult::Err(format!("function name must be a symbol, got {:?}", name)),
+    }
+}
+
+fn add_macro(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let usage = "Macro definitions must follow the pattern (defmacro name (arg1 arg2 .. argn) <Expr>)";
+    if vals.len() != 3 {
+        return EvalResult::Err(usage.into());
+    }
+    match (&*vals[0], &*vals[1]) {
+        (Expr::Symbol(name), Expr::List(params)) => {
+            let ps: Result<Vec<String>, String> = params.iter().map(|e| match &**e {
+                Expr::Symbol(n) => Ok(n.clone()),
+                _ => Err(format!("macro parameter must be a symbol, got {:?}", e)),
+            }).collect();
+            ps.map_or_else(
+                EvalResult::Err,
+                |ps| {
+                    env.macros.insert(name.clone(), (ps, vals[2].clone()));
+                    EvalResult::Unit
+                },
+            )
+        },
+        (Expr::Symbol(_), _) => EvalResult::Err(usage.into()),
+        (name, _) => EvalResult::Err(format!("macro name must be a symbol, got {:?}", name)),
+    }
+}
+
+/// Turns the value a macro produced back into code, so lists built with `cons` evaluate as calls.
+fn as_code(e: Rc<Expr>) -> Rc<Expr> {
+    match &*e {
+        Expr::List(_) | Expr::Pair(..) => {
+            let items: Vec<Rc<Expr>> = e.items().unwrap_or_default().into_iter().map(as_code).collect();
+            Expr::list(&items)
+        },
+        _ => e,
+    }
+}
+
+/// Runs the macro body with its parameters bound to the unevaluated arguments
+/// and evaluates the resulting code in place of the call.
+fn expand_macro(name: &str, (params, body): Binding, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if args.len() != params.len() {
+        return EvalResult::Err(format!("macro {} takes {} arguments, got {}", name, params.len(), args.len()));
+    }
+    // Bound values are evaluated on lookup, so quote the forms to hand them over as they are.
+    let quoted: Vec<Rc<Expr>> = args.iter().map(|a| Expr::list(&[Expr::symbol("quote"), a.clone()])).collect();
+    match in_call_to(name, call_fn(&params, body, &quoted, env)) {
+        EvalResult::Expr(expansion) => eval(as_code(expansion), env),
+        EvalResult::Unit => EvalResult::Err(format!("macro {} produced no expansion.", name)),
+        err => err,
     }
 }
 
@@ -206,14 +2265,15 @@ fn add_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
                 Expr::FNum(n) => Ok(*n),
                 _ => Err(format!("Can only sum numbers, got {:?}", exp)),
             },
-            _ => Err(format!("Failed to eval expr: {:?}", e)),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err(format!("Failed to eval expr: {:?}", e)),
         }).collect::<Result<Vec<f64>, String>>();
     total.map_or_else(
-        |err| EvalResult::Err(err),
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::fnum(xs.iter().sum())),
     )
 }
-fn subtract(vals: &Vec<f64>) -> f64 {
+fn subtract(vals: &[f64]) -> f64 {
     let mut sub = vals[0];
     vals[1..].iter().for_each(|x| {
         sub -= x;
@@ -230,11 +2290,11 @@ fn sub_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
                 Expr::FNum(n) => Ok(*n),
                 _ => Err(format!("Can only subtract numers, got {:?}", exp)),
             },
-            _ => Err(format!("Failed to eval expr: {:?}", e)),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err(format!("Failed to eval expr: {:?}", e)),
         }).collect::<Result<Vec<f64>, String>>();
-    let firstElement = true;
     total.map_or_else(
-        |err| EvalResult::Err(err),
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::fnum(subtract(&xs))),
     )
 }
@@ -249,17 +2309,18 @@ fn mul_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
                 Expr::FNum(n) => Ok(*n),
                 _ => Err(format!("Can only sum numers, got {:?}", exp)),
             },
-            _ => Err(format!("Failed to eval expr: {:?}", e)),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err(format!("Failed to eval expr: {:?}", e)),
         }).collect::<Result<Vec<f64>, String>>();
     total.map_or_else(
-        |err| EvalResult::Err(err),
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::fnum(xs.iter().product())),
     )
 }
-fn divide(vals: &Vec<f64>) -> f64{
+fn divide(vals: &[f64]) -> f64{
     let mut div = vals[0];
     vals[1..].iter().for_each(|x| {
-        div = div / x;
+        div /= x;
     });
     div
 }
@@ -273,68 +2334,39 @@ fn div_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
                 Expr::FNum(n) => Ok(*n),
                 _ => Err(format!("Can only sum numers, got {:?}", exp)),
             },
-            _ => Err(format!("Failed to eval expr: {:?}", e)),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err(format!("Failed to eval expr: {:?}", e)),
         }).collect::<Result<Vec<f64>, String>>();
     total.map_or_else(
-        |err| EvalResult::Err(err),
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::fnum(divide(&xs))),
     )
 }
-fn not(vals: &Rc<Expr>, env: &mut Environment) -> EvalResult {
-    match eval(vals.clone(), env) {
-            EvalResult::Err(e) => EvalResult::Err(format!("Failed to eval expr: {:?}", e)),
-            _ => match &*vals.clone() {
-                Expr::Symbol(s) => match &*s.as_str() {
-                    "True" => return EvalResult::Expr(Expr::symbol("False".into())),
-                    "False" => return EvalResult::Expr(Expr::symbol("True".into())),
-                    _ => return EvalResult::Err("Invalid input for not operator 1".into()),
-                },
-                Expr::List(l) => EvalResult::Expr(Expr::symbol(&is_equal_list(l))) ,
-                _ => EvalResult::Err("Invalid input for not operator 21".into()),
-            },
-            // EvalResult::Unit => match &*vals.clone() {
-            //     Expr::Symbol(s) => match &*s.as_str() {
-            //         "False" => EvalResult::Expr(Expr::symbol("True".into())),
-            //         "True" => EvalResult::Expr(Expr::symbol("False".into())),
-
-            //         _ => return EvalResult::Err("Invalid input for not operator 2".into()),
-            //     },
-            //     _ => EvalResult::Err("Not a symbol".into()),
-            // },
-
-            _ => EvalResult::Err("Failed to eval expr".into()),
+fn not(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err(format!("not takes exactly one argument, got {}", vals.len()));
+    }
+    match eval(vals[0].clone(), env) {
+        EvalResult::Expr(x) if is_truthy(&x) => EvalResult::Expr(Expr::symbol("False")),
+        EvalResult::Expr(_) => EvalResult::Expr(Expr::symbol("True")),
+        EvalResult::Unit => EvalResult::Err("not: argument has no value".into()),
+        err => err,
     }
-
-    
 }
 
-fn is_equal_symbol(vals: &Vec<Rc<Expr>>) -> String {
-    let comparer = &vals[0];
-    let mut hasFalse = false;
-    let val = vals[1..].iter().for_each(|x| {
-        if comparer == x {
-            
-        } else {
-            hasFalse = true;
-        }
-    });
-    if hasFalse {
+fn is_equal_symbol(vals: &[Rc<Expr>]) -> String {
+    let has_false = vals.split_first()
+        .is_some_and(|(comparer, rest)| rest.iter().any(|x| comparer != x));
+    if has_false {
         "True".into()
     } else {
         "False".into()
     }
 }
-fn is_equal_list(vals: &Vec<Rc<Expr>>) -> String {
-    let comparer = &vals[0];
-    let mut hasFalse: bool  = false;
-    let val = vals[1..].iter().for_each(|x| {
-        if comparer.eq(x)  {
-            
-        } else {
-            hasFalse = true
-        }
-    });
-    if hasFalse {
+fn is_equal_list(vals: &[Rc<Expr>]) -> String {
+    let has_false = vals.split_first()
+        .is_some_and(|(comparer, rest)| rest.iter().any(|x| !comparer.eq(x)));
+    if has_false {
         "False".into()
     } else {
         "True".into()
@@ -348,14 +2380,13 @@ fn equality(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
         .map(|e| match eval(e.clone(), env) {
             //valid expression
             EvalResult::Expr(exp) => Ok(exp),
-            EvalResult::Unit => Err(format!("Failed to eval, got Unit" )),
+            EvalResult::Unit => Err("Failed to eval, got Unit".to_string()),
             EvalResult::Err(e) => Err(format!("Failed to eval expr: {:?}", e)),
-            _ => Err("No idea whats wrong".into()),
         }).collect::<Result<Vec<Rc<Expr>>, String>>();
 
 
     total.map_or_else(
-        |err| EvalResult::Err(err) ,
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::symbol(&is_equal_list(&xs))),
     )
 }
@@ -367,56 +2398,203 @@ fn inequality(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
         .map(|e| match eval(e.clone(), env) {
             //valid expression
             EvalResult::Expr(exp) => Ok(exp),
-            EvalResult::Unit => Err(format!("Failed to eval, got Unit" )),
+            EvalResult::Unit => Err("Failed to eval, got Unit".to_string()),
             EvalResult::Err(e) => Err(format!("Failed to eval expr: {:?}", e)),
-            _ => Err("No idea whats wrong".into()),
         }).collect::<Result<Vec<Rc<Expr>>, String>>();
 
     total.map_or_else(
-        |err| EvalResult::Err(err),
+        EvalResult::Err,
         |xs| EvalResult::Expr(Expr::symbol(&is_equal_symbol(&xs))),
     )
 }
 
-fn bool_and(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    match vals.iter().all(|x| *x == vals[0]) {
-        true => EvalResult::Expr(Expr::symbol("True".into())),
-        false => EvalResult::Expr(Expr::symbol("False".into())),
+/// Checks that every adjacent pair of the numeric arguments satisfies `relation`.
+/// Each argument is evaluated exactly once.
+fn compare_chain(name: &str, vals: &[Rc<Expr>], env: &mut Environment, relation: fn(f64, f64) -> bool) -> EvalResult {
+    if vals.is_empty() {
+        return EvalResult::Err(format!("'{}' with no arguments", name));
+    }
+    let nums = vals.iter()
+        .map(|e| match eval(e.clone(), env) {
+            EvalResult::Expr(exp) => match &*exp {
+                Expr::FNum(n) => Ok(*n),
+                _ => Err(format!("Can only compare numbers with '{}', got {:?}", name, exp)),
+            },
+            EvalResult::Unit => Err("Failed to eval, got Unit".to_string()),
+            EvalResult::Err(e) => Err(format!("Failed to eval expr: {:?}", e)),
+        }).collect::<Result<Vec<f64>, String>>();
+    nums.map_or_else(
+        EvalResult::Err,
+        |xs| match xs.windows(2).all(|w| relation(w[0], w[1])) {
+            true => EvalResult::Expr(Expr::symbol("True")),
+            false => EvalResult::Expr(Expr::symbol("False")),
+        },
+    )
+}
+
+/// `and` and `or`: evaluates the arguments in order, stopping at the first whose truthiness
+/// is `decisive`, which is then the result. Otherwise the result is the opposite.
+fn bool_chain(op: &str, vals: &[Rc<Expr>], env: &mut Environment, decisive: bool) -> EvalResult {
+    if vals.is_empty() {
+        return EvalResult::Err(format!("{} takes at least one argument", op));
     }
+    let truth = |b: bool| EvalResult::Expr(Expr::symbol(if b { "True" } else { "False" }));
+    for val in vals {
+        match eval(val.clone(), env) {
+            EvalResult::Expr(x) if is_truthy(&x) == decisive => return truth(decisive),
+            EvalResult::Expr(_) => {},
+            EvalResult::Unit => return EvalResult::Err(format!("{}: argument has no value", op)),
+            err => return err,
+        }
+    }
+    truth(!decisive)
 }
-fn bool_or(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    match vals.iter().any(|x| *x == Expr::symbol("True")) {
-        true => EvalResult::Expr(Expr::symbol("True".into())),
-        false => EvalResult::Expr(Expr::symbol("False".into())),
+/// The empty list and the symbol `False` are falsey; every other value is truthy.
+pub fn is_truthy(expr: &Expr) -> bool {
+    match expr {
+        Expr::List(l) => !l.is_empty(),
+        Expr::Symbol(s) => s != "False",
+        _ => true,
     }
-
 }
+
+/// `(if test then else)`, or `(if test then)`, which gives Unit when `test` is falsey.
 fn if_then_else(blocks: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if blocks.len() != 3 {
-        return EvalResult::Err("Must have format: if (<argument>) (<then block>) (<else block>)".into())
+    match if_branch(blocks, env) {
+        Ok(Some(block)) => eval(block, env),
+        Ok(None) => EvalResult::Unit,
+        Err(err) => err,
+    }
+}
+
+/// Evaluates the test of an `if` and returns the branch it selects, or `None` when the
+/// test of a two-armed `if` is falsey.
+fn if_branch(blocks: &[Rc<Expr>], env: &mut Environment) -> Result<Option<Rc<Expr>>, EvalResult> {
+    if blocks.len() != 2 && blocks.len() != 3 {
+        return Err(EvalResult::Err("Must have format: if (<argument>) (<then block>) [(<else block>)]".into()))
     }
     match eval(blocks[0].clone(), env) {
-        EvalResult::Expr(expr) => {
-            match &*expr {
-                Expr::List(l) if l.len() == 0 => eval(blocks[2].clone(), env),
-                _ => eval(blocks[1].clone(), env),
-            };
-            eval(blocks[1].clone(), env)
+        EvalResult::Expr(expr) if is_truthy(&expr) => Ok(Some(blocks[1].clone())),
+        EvalResult::Expr(_) => Ok(blocks.get(2).cloned()),
+        EvalResult::Unit => Err(EvalResult::Err("If expression predicate must return an expression.".into())),
+        err => Err(err),
+    }
+}
+fn while_loop(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.is_empty() {
+        return EvalResult::Err("while must follow the pattern (while <condition> <body> ...)".into());
+    }
+    loop {
+        match eval(vals[0].clone(), env) {
+            EvalResult::Expr(cond) if is_truthy(&cond) => {},
+            EvalResult::Expr(_) => return EvalResult::Unit,
+            EvalResult::Unit => return EvalResult::Err("while condition must return an expression.".into()),
+            err => return err,
+        }
+        for body in &vals[1..] {
+            if let EvalResult::Err(err) = eval(body.clone(), env) {
+                return EvalResult::Err(err);
+            }
+        }
+    }
+}
+
+fn quote(vals: &[Rc<Expr>]) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("quote takes exactly one argument: (quote <expr>)".into());
+    }
+    EvalResult::Expr(vals[0].clone())
+}
+
+fn eval_quoted(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("eval takes exactly one argument: (eval <expr>)".into());
+    }
+    match eval(vals[0].clone(), env) {
+        EvalResult::Expr(code) => eval(code, env),
+        EvalResult::Unit => EvalResult::Err("cannot eval Unit.".into()),
+        err => err,
+    }
+}
+
+/// Whether a value can be called through `apply_fn`: a function value, the name of a
+/// user function, or the name of one of the `BUILTINS` that nothing has shadowed.
+fn is_callable(f: &Expr, env: &Environment) -> bool {
+    match f {
+        Expr::Lambda(..) | Expr::Partial(..) => true,
+        Expr::Symbol(s) => match env.lookup(s) {
+            Some((params, _)) => !params.is_empty(),
+            None => BUILTINS.contains(&s.as_str()),
         },
-        EvalResult::Unit => EvalResult::Err("If expression predicate must return an expression.".into()),
-        err => err
+        _ => false,
     }
 }
+
 /// Evaluates the given expression.
+///
+/// Builtin operators are only recognized in call position. A symbol in value
+/// position is always resolved through the environment, so `(let + 5)` makes `+`
+/// read as 5 while `(+ 1 2)` still adds. Unbound, `+` reads as a two-argument
+/// function value (see `OPERATORS`).
 pub fn eval(e: Rc<Expr>, env: &mut Environment) -> EvalResult {
+    if !env.trace {
+        return eval_step(e, env);
+    }
+    let depth = env.trace_depth;
+    let indent = "  ".repeat(depth);
+    if let Err(err) = env.write_output(&format!("{}[{}] {}\n", indent, depth, flat(&e))) {
+        return EvalResult::Err(err);
+    }
+    env.trace_depth += 1;
+    let result = eval_step(e, env);
+    env.trace_depth = depth;
+    let shown = match &result {
+        EvalResult::Expr(x) => flat(x),
+        EvalResult::Unit => "Unit".into(),
+        EvalResult::Err(err) => format!("error: {}", err),
+    };
+    env.write_output(&format!("{}[{}] => {}\n", indent, depth, shown))
+        .map_or_else(EvalResult::Err, |_| result)
+}
+
+/// Evaluates the given expression without tracing it.
+fn eval_step(e: Rc<Expr>, env: &mut Environment) -> EvalResult {
+    if let Err(err) = env.tick() {
+        return EvalResult::Err(err);
+    }
     match &*e{
-        Expr::FNum(_) => EvalResult::Expr(e.clone()),
+        Expr::FNum(_) | Expr::Str(_) | Expr::Char(_) | Expr::Lambda(..) | Expr::Pair(..) | Expr::Promise(..) | Expr::Partial(..) | Expr::Map(_) | Expr::Values(_) => EvalResult::Expr(e.clone()),
+        Expr::Symbol(s) if s == "unit" => EvalResult::Unit,
         Expr::Symbol(s) => eval_symbol(e.clone(), s, &[], env),
         Expr::List(vals) => {
             if vals.is_empty() {
                 return EvalResult::Expr(Expr::list(&[]));
             }
             let op = &*vals[0];
+            let binding = match op {
+                Expr::Symbol(s) => env.lookup(s),
+                _ => None,
+            };
+            if let Expr::Symbol(s) = op {
+                if let Some(mac) = env.macros.get(s).cloned() {
+                    return expand_macro(s, mac, &vals[1..], env);
+                }
+                let is_user_fn = binding.as_ref().is_some_and(|(params, _)| !params.is_empty());
+                if is_user_fn && !SPECIAL_FORMS.contains(&s.as_str()) {
+                    return eval_binding(e.clone(), s, binding, &vals[1..], env);
+                }
+                // A variable holding a function value is called like a function. Operators
+                // skip this so direct calls always go to the variadic builtin, even when a
+                // variable of the same name holds a function.
+                if let Some((_, value)) = binding.as_ref().filter(|(params, value)| {
+                    params.is_empty() && matches!(**value, Expr::Lambda(..) | Expr::Partial(..))
+                }) {
+                    if !SPECIAL_FORMS.contains(&s.as_str()) && !OPERATORS.contains(&s.as_str()) {
+                        let f = value.clone();
+                        return eval_args(&vals[1..], env).map_or_else(EvalResult::Err, |args| apply_fn(f, &args, env));
+                    }
+                }
+            }
             match op {
                 Expr::Symbol(s) if s == "+" => add_vals(&vals[1..], env),
                 Expr::Symbol(s) if s == "-" => sub_vals(&vals[1..], env),
@@ -424,23 +2602,130 @@ pub fn eval(e: Rc<Expr>, env: &mut Environment) -> EvalResult {
                 Expr::Symbol(s) if s == "/" => div_vals(&vals[1..], env),
                 Expr::Symbol(s) if s == "=" => equality(&vals[1..], env),
                 Expr::Symbol(s) if s == "!=" => inequality(&vals[1..], env),
-                Expr::Symbol(s) if s == "not" => not(&vals[1], env),
-                Expr::Symbol(s) if s == "and" => bool_and(&vals[1..], env),
-                Expr::Symbol(s) if s == "or" => bool_or(&vals[1..], env),
+                Expr::Symbol(s) if s == "<" => compare_chain(s, &vals[1..], env, |a, b| a < b),
+                Expr::Symbol(s) if s == ">" => compare_chain(s, &vals[1..], env, |a, b| a > b),
+                Expr::Symbol(s) if s == "<=" => compare_chain(s, &vals[1..], env, |a, b| a <= b),
+                Expr::Symbol(s) if s == ">=" => compare_chain(s, &vals[1..], env, |a, b| a >= b),
+                Expr::Symbol(s) if s == "not" => not(&vals[1..], env),
+                Expr::Symbol(s) if s == "and" => bool_chain(s, &vals[1..], env, false),
+                Expr::Symbol(s) if s == "or" => bool_chain(s, &vals[1..], env, true),
                 Expr::Symbol(s) if s == "fn" => add_fn_to_env(&vals[1..], env),
-                Expr::Symbol(s) if s == "let" => add_var_to_env(&vals[1..], env),
+                Expr::Symbol(s) if s == "defmacro" => add_macro(&vals[1..], env),
+                Expr::Symbol(s) if s == "let" => add_var_to_env(s, &vals[1..], env, false),
+                Expr::Symbol(s) if s == "var" => add_var_to_env(s, &vals[1..], env, true),
+                Expr::Symbol(s) if s == "set!" => set_var_in_env(&vals[1..], env),
+                Expr::Symbol(s) if s == "undef" => undef(&vals[1..], env),
                 Expr::Symbol(s) if s == "print" => {
                     let output: Vec<String> = vals[1..]
                         .iter().cloned()
                         .map(|expr| gen_print_output(expr, env)).collect();
-                    println!("{}", output.join(" "));
-                    EvalResult::Unit
+                    env.write_output(&format!("{}\n", output.join(" ")))
+                        .map_or_else(EvalResult::Err, |_| EvalResult::Unit)
                 }
                 Expr::Symbol(s) if s == "if" => if_then_else(&vals[1..], env),
-                Expr::Symbol(s) if env.contains_key(&s) => eval_symbol(e.clone(), s, &vals[1..], env),
+                Expr::Symbol(s) if s == "while" => while_loop(&vals[1..], env),
+                Expr::Symbol(s) if s == "quote" => quote(&vals[1..]),
+                Expr::Symbol(s) if s == "eval" => eval_quoted(&vals[1..], env),
+                Expr::Symbol(s) if s == "function" => function_value(&vals[1..], env),
+                Expr::Symbol(s) if s == "map" => map_fn(&vals[1..], env),
+                Expr::Symbol(s) if s == "apply" => apply_vals(&vals[1..], env),
+                Expr::Symbol(s) if s == "with-output" => with_output(&vals[1..], env),
+                Expr::Symbol(s) if s == "char->number" => char_to_number(&vals[1..], env),
+                Expr::Symbol(s) if s == "number->char" => number_to_char(&vals[1..], env),
+                Expr::Symbol(s) if s == "string->list" => string_to_list(&vals[1..], env),
+                Expr::Symbol(s) if s == "list->string" => list_to_string(&vals[1..], env),
+                Expr::Symbol(s) if s == "random" => random(&vals[1..], env),
+                Expr::Symbol(s) if s == "seed!" => seed_rng(&vals[1..], env),
+                Expr::Symbol(s) if s == "ast-size" => ast_size(&vals[1..], env),
+                Expr::Symbol(s) if s == "slice" => slice(&vals[1..], env),
+                Expr::Symbol(s) if s == "iterate" => iterate(&vals[1..], env),
+                Expr::Symbol(s) if s == "values" => values(&vals[1..], env),
+                Expr::Symbol(s) if s == "divmod" => divmod(&vals[1..], env),
+                Expr::Symbol(s) if s == "let-values" => let_values(&vals[1..], env),
+                Expr::Symbol(s) if s == "flatten" => flatten(&vals[1..], env),
+                Expr::Symbol(s) if s == "take" => split_list(s, &vals[1..], env, true),
+                Expr::Symbol(s) if s == "drop" => split_list(s, &vals[1..], env, false),
+                Expr::Symbol(s) if s == "count" => count(&vals[1..], env),
+                Expr::Symbol(s) if s == "enumerate" => enumerate(&vals[1..], env),
+                Expr::Symbol(s) if s == "delay" => delay(&vals[1..]),
+                Expr::Symbol(s) if s == "force" => force(&vals[1..], env),
+                Expr::Symbol(s) if s == "%" => remainder(s, &vals[1..], env, |n, d| n % d),
+                Expr::Symbol(s) if s == "mod-euclid" => remainder(s, &vals[1..], env, f64::rem_euclid),
+                Expr::Symbol(s) if s == "pprint" => pprint(&vals[1..], env),
+                Expr::Symbol(s) if s == "length" => length(&vals[1..], env),
+                Expr::Symbol(s) if s == "reverse" => reverse(&vals[1..], env),
+                Expr::Symbol(s) if s == "insert" => insert(&vals[1..], env),
+                Expr::Symbol(s) if s == "remove" => remove(&vals[1..], env),
+                Expr::Symbol(s) if s == "approx=" => approx_equal(&vals[1..], env),
+                Expr::Symbol(s) if s == "unfold" => unfold(&vals[1..], env),
+                Expr::Symbol(s) if s == "partition" => partition(&vals[1..], env),
+                Expr::Symbol(s) if s == "deadline" => deadline(&vals[1..], env),
+                Expr::Symbol(s) if s == "every?" => every(&vals[1..], env),
+                Expr::Symbol(s) if s == "some?" => some(&vals[1..], env),
+                Expr::Symbol(s) if s == "make-counter" => make_counter(&vals[1..]),
+                Expr::Symbol(s) if s == "sum" => fold_numbers(s, &vals[1..], env, 0.0, |a, b| a + b),
+                Expr::Symbol(s) if s == "product" => fold_numbers(s, &vals[1..], env, 1.0, |a, b| a * b),
+                Expr::Symbol(s) if s == "format" => format_string(&vals[1..], env),
+                Expr::Symbol(s) if s == "tree-approx=" => tree_approx_equal(&vals[1..], env),
+                Expr::Symbol(s) if s == "depth" => depth(&vals[1..], env),
+                Expr::Symbol(s) if s == "reduce" => reduce(&vals[1..], env),
+                Expr::Symbol(s) if s == "chunk" => chunk(&vals[1..], env),
+                Expr::Symbol(s) if s == "distinct" => distinct(&vals[1..], env),
+                Expr::Symbol(s) if s == "product-pairs" => product_pairs(&vals[1..], env),
+                Expr::Symbol(s) if s == "alist->map" => alist_to_map(&vals[1..], env),
+                Expr::Symbol(s) if s == "map->alist" => map_to_alist(&vals[1..], env),
+                Expr::Symbol(s) if s == "minmax" => minmax(&vals[1..], env),
+                Expr::Symbol(s) if s == "starts-with?" => affix_of(s, &vals[1..], env, true),
+                Expr::Symbol(s) if s == "ends-with?" => affix_of(s, &vals[1..], env, false),
+                Expr::Symbol(s) if s == "read-eval-string" => read_eval_string(&vals[1..], env),
+                Expr::Symbol(s) if s == "curry" => curry(&vals[1..], env),
+                Expr::Symbol(s) if s == "compose" => compose(&vals[1..], env),
+                Expr::Symbol(s) if s == "repeat" => repeat(&vals[1..], env),
+                Expr::Symbol(s) if s == "string->number-or" => string_to_number_or(&vals[1..], env),
+                Expr::Symbol(s) if s == "bound?" => is_bound(&vals[1..], env),
+                Expr::Symbol(s) if s == "gcd" => fold_integers(s, &vals[1..], env, gcd_of),
+                Expr::Symbol(s) if s == "lcm" => fold_integers(s, &vals[1..], env, lcm_of),
+                Expr::Symbol(s) if s == "divides?" => divides(&vals[1..], env),
+                Expr::Symbol(s) if s == "factorial" => factorial(&vals[1..], env),
+                Expr::Symbol(s) if s == "expt" => expt(&vals[1..], env),
+                Expr::Symbol(s) if s == "values-of" => values_of(&vals[1..], env),
+                Expr::Symbol(s) if s == "print-each" => print_each(&vals[1..], env),
+                Expr::Symbol(s) if s == "paths" => paths(&vals[1..], env),
+                Expr::Symbol(s) if s == "list-depth" => list_depth(&vals[1..], env),
+                Expr::Symbol(s) if s == "car-or" => car_or(&vals[1..], env),
+                Expr::Symbol(s) if s == "type-of" => type_of(&vals[1..], env),
+                Expr::Symbol(s) if s == "swap" => swap(&vals[1..], env),
+                Expr::Symbol(s) if s == "fold-until" => fold_until(&vals[1..], env),
+                Expr::Symbol(s) if s == "interleave" => interleave(&vals[1..], env),
+                Expr::Symbol(s) if s == "lookup" => lookup_binding(&vals[1..], env),
+                Expr::Symbol(s) if s == "load" => load(&vals[1..], env),
+                Expr::Symbol(s) if s == "frequencies" => frequencies(&vals[1..], env),
+                Expr::Symbol(s) if s == "scan" => scan(&vals[1..], env),
+                Expr::Symbol(s) if s == "unit?" => is_unit(&vals[1..], env),
+                Expr::Symbol(s) if s == "transpose" => transpose(&vals[1..], env),
+                Expr::Symbol(s) if s == "index-of" => index_of(&vals[1..], env),
+                Expr::Symbol(s) if s == "map-pairs" => map_pairs(&vals[1..], env),
+                Expr::Symbol(s) if s == "map-indexed" => map_indexed(&vals[1..], env),
+                Expr::Symbol(s) if s == "car" => car(&vals[1..], env),
+                Expr::Symbol(s) if s == "cdr" => cdr(&vals[1..], env),
+                Expr::Symbol(s) if s == "cons" => cons(&vals[1..], env),
+                Expr::Symbol(s) if binding.is_some() => eval_binding(e.clone(), s, binding, &vals[1..], env),
                 _ => {
-                    let res: Result<Vec<Rc<Expr>>, EvalResult> = vals.iter().cloned()
-                    .map(|x| eval(x, env))
+                    // A computed head that yields a function is called with the remaining elements.
+                    let (head, rest) = match op {
+                        Expr::List(_) => match eval(vals[0].clone(), env) {
+                            EvalResult::Expr(f) if is_callable(&f, env) => {
+                                return eval_args(&vals[1..], env).map_or_else(
+                                    EvalResult::Err,
+                                    |args| apply_fn(f, &args, env),
+                                );
+                            },
+                            head => (Some(head), &vals[1..]),
+                        },
+                        _ => (None, &vals[..]),
+                    };
+                    let res: Result<Vec<Rc<Expr>>, EvalResult> = head.into_iter()
+                    .chain(rest.iter().cloned().map(|x| eval(x, env)))
                     .filter(|x| *x != EvalResult::Unit)
                     .map(|x| if let EvalResult::Expr(expr) = x {
                         Ok(expr)
@@ -467,7 +2752,7 @@ mod tests {
     fn cannot_add_to_contextless_env(){
         let mut env = Environment::empty();
         let r = env.add_var("a", Expr::fnum(1.0));
-        assert!( r.is_err(), format!("Expected add_var to fail, but it succeeded: {:?}", r) );
+        assert!(r.is_err(), "Expected add_var to fail, but it succeeded: {:?}", r);
     }
     #[test]
     fn can_add_var_to_context_env() {
@@ -477,10 +2762,10 @@ mod tests {
         env.push_context();
         assert_eq!(1usize, env.num_contexts());
 
-        env.add_var("a", Expr::fnum(1.0));
+        let _ = env.add_var("a", Expr::fnum(1.0));
 
         env.lookup("a").map_or_else(
-            || assert!(false, "Failed to find var in environment."),
+            || panic!("Failed to find var in environment."),
             |(param, x)| {
                 assert_eq!(val, x);
                 assert_eq!(0usize, param.len());
@@ -488,7 +2773,9 @@ mod tests {
         );
 
         env.pop_context();
-        env.lookup("a").map(|x| assert!(false, format!("Expected Err, got {:?}", x)));
+        if let Some(x) = env.lookup("a") {
+            panic!("Expected Err, got {:?}", x);
+        }
         assert_eq!(0usize, env.num_contexts());
     }
 
@@ -496,14 +2783,14 @@ mod tests {
     fn default_environment_is_correct() {
         let env = Environment::default();
         env.lookup("False").map_or_else(
-            || assert!(false, "Expected Some, got None 1"),
+            || panic!("Expected Some, got None 1"),
             |(ps, expr)| {
                 assert_eq!(0, ps.len());
                 assert_eq!(Expr::list(&[]), expr);
             },
         );
         env.lookup("True").map_or_else(
-            || assert!(false, "Expected Some, got None 2"),
+            || panic!("Expected Some, got None 2"),
             |(ps, expr)| {
                 assert_eq!(0, ps.len());
                 assert_eq!(Expr::list(&[Expr::fnum(1.0)]), expr);
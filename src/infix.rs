@@ -0,0 +1,140 @@
+use crate::lex::Token;
+use crate::parse::ParseError;
+use std::collections::HashMap;
+
+/// How repeated uses of an operator group: `1 - 2 - 3` is `[[1 - 2] - 3]` while
+/// `2 ^ 3 ^ 2` is `[2 ^ [3 ^ 2]]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+/// An infix operator: the function it is rewritten to, how tightly it binds (higher binds
+/// tighter) and how it groups with itself.
+#[derive(Debug, Clone)]
+pub struct Operator {
+    pub function: String,
+    pub precedence: u8,
+    pub assoc: Assoc,
+}
+
+/// Infix operators by the symbol written between the operands.
+pub type OperatorTable = HashMap<String, Operator>;
+
+/// `+ -` below `* /` below a right associative `^`, which is rewritten to `expt`.
+pub fn default_operators() -> OperatorTable {
+    [
+        ("+", "+", 1, Assoc::Left),
+        ("-", "-", 1, Assoc::Left),
+        ("*", "*", 2, Assoc::Left),
+        ("/", "/", 2, Assoc::Left),
+        ("^", "expt", 3, Assoc::Right),
+    ].iter().map(|&(symbol, function, precedence, assoc)| {
+        (symbol.to_string(), Operator { function: function.to_string(), precedence, assoc })
+    }).collect()
+}
+
+/// An optional reader pass run before `parse`: rewrites each bracketed infix expression
+/// in `tokens` into prefix form using `operators`, so `[1 + 2 * 3]` reads as `(+ 1 (* 2 3))`.
+/// Tokens outside brackets are copied unchanged. Inside brackets, parentheses and nested
+/// brackets only group, as in `[(1 + 2) * 3]`.
+pub fn rewrite_infix(tokens: &[Token], operators: &OperatorTable) -> Result<Vec<Token>, ParseError> {
+    let tokens = split_brackets(tokens);
+    let mut out = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if is_literal(&tokens[index], "[") {
+            let (expr, after) = infix_expr(&tokens, index + 1, 0, operators)?;
+            out.extend(expr);
+            index = expect_closing(&tokens, after, "]")?;
+        } else if is_literal(&tokens[index], "]") {
+            return Err(ParseError::BadParse("Unexpected ] encountered.".into()));
+        } else {
+            out.push(tokens[index].clone());
+            index += 1;
+        }
+    }
+    Ok(out)
+}
+
+fn is_literal(token: &Token, s: &str) -> bool {
+    matches!(token, Token::Literal(l) if l == s)
+}
+
+/// The lexer leaves brackets attached to neighbouring literals, as in `[1`, so split them off.
+fn split_brackets(tokens: &[Token]) -> Vec<Token> {
+    let mut out = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Literal(s) if s.contains(['[', ']']) => {
+                let mut part = String::new();
+                for c in s.chars() {
+                    if c == '[' || c == ']' {
+                        if !part.is_empty() {
+                            out.push(Token::Literal(std::mem::take(&mut part)));
+                        }
+                        out.push(Token::Literal(c.to_string()));
+                    } else {
+                        part.push(c);
+                    }
+                }
+                if !part.is_empty() {
+                    out.push(Token::Literal(part));
+                }
+            },
+            t => out.push(t.clone()),
+        }
+    }
+    out
+}
+
+/// Returns the index after the `closing` token expected at `index`.
+fn expect_closing(tokens: &[Token], index: usize, closing: &str) -> Result<usize, ParseError> {
+    match tokens.get(index) {
+        None => Err(ParseError::Incomplete),
+        Some(Token::RPar) if closing == ")" => Ok(index + 1),
+        Some(t) if is_literal(t, closing) => Ok(index + 1),
+        Some(t) => Err(ParseError::BadParse(format!("expected {} in infix expression, got {:?}", closing, t))),
+    }
+}
+
+/// Reads one operand: an atom, or a group in parentheses or brackets.
+fn operand(tokens: &[Token], index: usize, operators: &OperatorTable) -> Result<(Vec<Token>, usize), ParseError> {
+    let closing = match tokens.get(index) {
+        None => return Err(ParseError::Incomplete),
+        Some(Token::LPar) => ")",
+        Some(t) if is_literal(t, "[") => "]",
+        Some(Token::Literal(s)) if operators.contains_key(s) || s == "]" => {
+            return Err(ParseError::BadParse(format!("expected an operand in infix expression, got {}", s)));
+        },
+        Some(Token::RPar) => return Err(ParseError::BadParse("Unexpected ) encountered.".into())),
+        Some(t) => return Ok((vec![t.clone()], index + 1)),
+    };
+    let (expr, after) = infix_expr(tokens, index + 1, 0, operators)?;
+    Ok((expr, expect_closing(tokens, after, closing)?))
+}
+
+/// Precedence climbing: reads operands joined by operators that bind at least as tightly
+/// as `min_precedence`, and returns their prefix tokens and the index after them.
+fn infix_expr(tokens: &[Token], index: usize, min_precedence: u8, operators: &OperatorTable) -> Result<(Vec<Token>, usize), ParseError> {
+    let (mut lhs, mut index) = operand(tokens, index, operators)?;
+    while let Some(Token::Literal(symbol)) = tokens.get(index) {
+        let op = match operators.get(symbol) {
+            Some(op) if op.precedence >= min_precedence => op,
+            _ => break,
+        };
+        let next_min = match op.assoc {
+            Assoc::Left => op.precedence + 1,
+            Assoc::Right => op.precedence,
+        };
+        let (rhs, after) = infix_expr(tokens, index + 1, next_min, operators)?;
+        let mut call = vec![Token::LPar, Token::Literal(op.function.clone())];
+        call.extend(lhs);
+        call.extend(rhs);
+        call.push(Token::RPar);
+        lhs = call;
+        index = after;
+    }
+    Ok((lhs, index))
+}
@@ -1,26 +1,76 @@
-use crate::types::Expr;
-use crate::lex::lex;
-use crate::parse::parse;
+use crate::types::{to_json, Expr};
+use crate::lex::{lex_spanned, Token};
+use crate::parse::parse_with_position;
 use crate::eval::{eval, Environment, EvalResult};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Renders `message` followed by the source line containing byte offset `pos`
+/// and a caret under the offending column.
+pub fn render_diagnostic(source: &str, pos: usize, message: &str) -> String {
+    let pos = pos.min(source.len());
+    let line_start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[pos..].find('\n').map_or(source.len(), |i| pos + i);
+    let line_no = source[..pos].matches('\n').count() + 1;
+    let column = source[line_start..pos].chars().count() + 1;
+    format!(
+        "{}\n --> line {}, column {}\n{}\n{}^",
+        message,
+        line_no,
+        column,
+        &source[line_start..line_end],
+        " ".repeat(column - 1),
+    )
+}
+
+/// Lexes and parses the program, rendering parse errors with a source excerpt.
+fn parse_source(program: &str) -> Result<Rc<Expr>, String> {
+    let spanned = lex_spanned(program).map_err(|e| format!("Lex error: {:?}", e))?;
+    let (tokens, spans): (Vec<Token>, Vec<_>) = spanned.into_iter().unzip();
+    parse_with_position(&tokens).map_err(|(index, e)| {
+        let pos = spans.get(index).map_or(program.trim_end().len(), |span| span.start);
+        render_diagnostic(program, pos, &format!("Parse error: {:?}", e))
+    })
+}
 
 /// Lexes, parses, and evaluates the given program.
 pub fn run_interpreter(program: &str) -> EvalResult {
-    match lex(&program){
-        Err(e) => EvalResult::Err(format!("Lex error: {:?}", e)),
-        Ok(tokens) => match parse(&tokens) {
-            Err(e) => EvalResult::Err(format!("Parse error: {:?}", e)),
-            Ok(expr) => {
-                let mut env = Environment::default();
-                match eval(expr.clone(), &mut env) {
-                    EvalResult::Err(e) => EvalResult::Err(e),
-                    EvalResult::Expr(expr) => match &*expr.clone() {
-                        Expr::Symbol(s) => EvalResult::Expr(Expr::symbol(&s)),
-                        Expr::FNum(n) => EvalResult::Expr(Expr::fnum(*n)),
-                        Expr::List(l) => EvalResult::Expr(Expr::list(&l)),
-                    } ,
-                    EvalResult::Unit => EvalResult::Unit ,
-                }
-            },
+    let mut env = Environment::default();
+    run_interpreter_with_env(program, &mut env)
+}
+
+/// Like `run_interpreter`, but aborts with an error once `timeout` has elapsed.
+pub fn run_interpreter_with_timeout(program: &str, timeout: Duration) -> EvalResult {
+    let mut env = Environment {
+        deadline: Some(Instant::now() + timeout),
+        ..Environment::default()
+    };
+    run_interpreter_with_env(program, &mut env)
+}
+
+/// Lexes, parses, and evaluates the given program in an existing environment.
+pub fn run_interpreter_with_env(program: &str, env: &mut Environment) -> EvalResult {
+    match parse_source(program) {
+        Err(e) => EvalResult::Err(e),
+        Ok(expr) => {
+            match eval(expr.clone(), env) {
+                EvalResult::Err(e) => EvalResult::Err(e),
+                EvalResult::Expr(expr) => match &*expr.clone() {
+                    Expr::Symbol(s) => EvalResult::Expr(Expr::symbol(s)),
+                    Expr::FNum(n) => EvalResult::Expr(Expr::fnum(*n)),
+                    Expr::Str(s) => EvalResult::Expr(Expr::string(s)),
+                    Expr::Char(c) => EvalResult::Expr(Expr::character(*c)),
+                    Expr::List(l) => EvalResult::Expr(Expr::list(l)),
+                    Expr::Pair(head, tail) => EvalResult::Expr(Expr::cons(head.clone(), tail.clone())),
+                    Expr::Lambda(..) | Expr::Promise(..) | Expr::Partial(..) | Expr::Map(_) | Expr::Values(_) => EvalResult::Expr(expr),
+                } ,
+                EvalResult::Unit => EvalResult::Unit ,
+            }
         },
     }
 }
+
+/// Lexes and parses the given program and returns its syntax tree as JSON, without evaluating it.
+pub fn run_interpreter_ast(program: &str) -> Result<String, String> {
+    parse_source(program).map(|expr| to_json(&expr))
+}
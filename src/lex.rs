@@ -1,14 +1,21 @@
-#[derive(Debug)]
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone)]
 pub enum Token {
     LPar,
     RPar,
     Literal(String),
+    Str(String),
+    Char(char),
 }
 
 impl PartialEq for Token {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Token::Literal(l1), Token::Literal(l2)) => l1 == l2,
+            (Token::Str(s1), Token::Str(s2)) => s1 == s2,
+            (Token::Char(c1), Token::Char(c2)) => c1 == c2,
             (Token::LPar, Token::LPar)
             | (Token::RPar, Token::RPar) => true,
             _ => false,
@@ -16,9 +23,17 @@ impl PartialEq for Token {
     }
 }
 
+/// Byte offsets of a token in the source: `start` is inclusive and `end` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug)]
 pub enum LexError {
     UnknownToken(String),
+    UnterminatedString,
 }
 
 fn add_whitespace(input: &str) -> String{
@@ -33,16 +48,88 @@ fn add_whitespace(input: &str) -> String{
     }).iter().collect()
 }
 
-pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
-    
+fn lex_code(input: &str, offset: usize) -> Vec<(Token, Span)> {
+    let mut cursor = 0;
     add_whitespace(input).split_ascii_whitespace().map(|p| {
-        match p {
-            "(" => Ok(Token::LPar),
-            ")" => Ok(Token::RPar),
-            _ => Ok(Token::Literal(p.into())),
+        let start = cursor + input[cursor..].find(p).unwrap_or(0);
+        cursor = start + p.len();
+        let token = match p {
+            "(" => Token::LPar,
+            ")" => Token::RPar,
+            _ => Token::Literal(p.into()),
+        };
+        (token, Span { start: offset + start, end: offset + cursor })
+    }).collect()
+}
 
+/// Reads a string literal whose opening quote has already been consumed.
+/// Returns the token and the offset just past the closing quote.
+fn lex_string(chars: &mut Peekable<CharIndices>) -> Result<(Token, usize), LexError> {
+    let mut s = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Token::Str(s), i + 1)),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => s.push('\n'),
+                Some((_, 't')) => s.push('\t'),
+                Some((_, c)) => s.push(c),
+                None => break,
+            },
+            _ => s.push(c),
         }
-    }).collect()
+    }
+    Err(LexError::UnterminatedString)
+}
+
+/// Reads a character literal such as `#\a` or `#\space` whose `#\` has already been consumed.
+/// Returns the token and the offset just past the literal.
+fn lex_char(chars: &mut Peekable<CharIndices>) -> Result<(Token, usize), LexError> {
+    let (i, first) = chars.next().ok_or_else(|| LexError::UnknownToken("#\\".into()))?;
+    let mut name = first.to_string();
+    let mut end = i + first.len_utf8();
+    if first.is_alphabetic() {
+        while let Some((j, c)) = chars.peek().copied().filter(|(_, c)| c.is_alphanumeric()) {
+            name.push(c);
+            end = j + c.len_utf8();
+            chars.next();
+        }
+    }
+    let token = match name.as_str() {
+        "space" => Token::Char(' '),
+        "newline" => Token::Char('\n'),
+        "tab" => Token::Char('\t'),
+        _ if name.chars().count() == 1 => Token::Char(first),
+        _ => return Err(LexError::UnknownToken(format!("#\\{}", name))),
+    };
+    Ok((token, end))
+}
+
+/// Lexes the input, pairing each token with its position in the source.
+pub fn lex_spanned(input: &str) -> Result<Vec<(Token, Span)>, LexError> {
+    let mut tokens = Vec::new();
+    let mut code_start = 0;
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let lexed = if c == '"' {
+            Some(lex_string(&mut chars)?)
+        } else if c == '#' && chars.peek().map(|(_, c)| *c) == Some('\\') {
+            chars.next();
+            Some(lex_char(&mut chars)?)
+        } else {
+            None
+        };
+        if let Some((token, end)) = lexed {
+            tokens.append(&mut lex_code(&input[code_start..i], code_start));
+            tokens.push((token, Span { start: i, end }));
+            code_start = end;
+        }
+    }
+    tokens.append(&mut lex_code(&input[code_start..], code_start));
+    Ok(tokens)
+}
+
+pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
+    lex_spanned(input).map(|tokens| tokens.into_iter().map(|(token, _)| token).collect())
 }
 
 #[cfg(test)]
@@ -52,7 +139,7 @@ mod test {
     fn test_whitespace() {
         let input = "()";
         let expected = " (  ) ";
-        let actual = add_whitespace(&input);
+        let actual = add_whitespace(input);
         assert_eq!(expected, actual);
     }
 
@@ -68,7 +155,21 @@ mod test {
         ];
         match lex(input){
             Ok(actual) => assert_eq!(expected, actual),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
+
+    #[test]
+    fn test_lex_spanned() {
+        let spans: Vec<Span> = lex_spanned("(f \"a b\" #\\c)").unwrap()
+            .into_iter().map(|(_, span)| span).collect();
+        let expected = vec![
+            Span { start: 0, end: 1 },
+            Span { start: 1, end: 2 },
+            Span { start: 3, end: 8 },
+            Span { start: 9, end: 12 },
+            Span { start: 12, end: 13 },
+        ];
+        assert_eq!(expected, spans);
+    }
 }
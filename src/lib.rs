@@ -1,9 +1,11 @@
 
 pub mod eval;
+pub mod infix;
 pub mod interpreter;
 pub mod lex;
 pub mod types;
 pub mod parse;
+pub mod repl;
 
 #[cfg(test)]
 mod tests {
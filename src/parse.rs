@@ -1,4 +1,4 @@
-use crate::lex::Token;
+use crate::lex::{Span, Token};
 use crate::types::Expr;
 use std::rc::Rc;
 
@@ -6,30 +6,140 @@ use std::rc::Rc;
 pub enum ParseError {
     BadParse(String),
     EOF,
+    /// The input ended inside an open list: it is a valid start of a form that more input could complete.
+    Incomplete,
+}
+
+/// A parenthesis without a partner, as reported by `check_balance`.
+#[derive(Debug, PartialEq)]
+pub enum BalanceError {
+    /// A `(` that is never closed.
+    Unclosed,
+    /// A `)` with no open `(` to close.
+    Unexpected,
 }
 
 #[derive(Debug)]
 pub enum ParseResult {
     Success(usize, Rc<Expr>),
-    Failure(ParseError),
+    /// The index of the token the error refers to and the error itself.
+    Failure(usize, ParseError),
 }
 
+/// Parses exactly one top-level form, failing if any tokens remain after it.
 pub fn parse(tokens: &[Token]) -> Result<Rc<Expr>, ParseError> {
+    parse_with_position(tokens).map_err(|(_, err)| err)
+}
+
+/// Like `parse`, but a failure also reports the index of the offending token.
+/// Running out of tokens is reported at `tokens.len()`.
+pub fn parse_with_position(tokens: &[Token]) -> Result<Rc<Expr>, (usize, ParseError)> {
     match parser(tokens, 0) {
+        ParseResult::Success(index, _) if tokens[index..].first() == Some(&Token::RPar) => {
+            Err((index, ParseError::BadParse("Unexpected ) encountered.".into())))
+        },
+        ParseResult::Success(index, _) if index < tokens.len() => {
+            Err((index, ParseError::BadParse("unexpected trailing tokens".into())))
+        },
         ParseResult::Success(_, expr) => Ok(expr),
-        ParseResult::Failure(err) => Err(err),
+        ParseResult::Failure(index, err) => Err((index, err)),
+    }
+}
+
+/// A parsed expression together with the source span it was read from.
+/// `children` holds the spans of a list's elements, in order, and is empty for atoms.
+#[derive(Debug)]
+pub struct SpannedExpr {
+    pub expr: Rc<Expr>,
+    pub span: Span,
+    pub children: Vec<SpannedExpr>,
+}
+
+impl SpannedExpr {
+    /// Returns the innermost form whose span contains the byte offset `pos`.
+    pub fn enclosing(&self, pos: usize) -> Option<&SpannedExpr> {
+        if pos < self.span.start || pos >= self.span.end {
+            return None;
+        }
+        self.children.iter()
+            .find_map(|child| child.enclosing(pos))
+            .or(Some(self))
+    }
+}
+
+/// Like `parse_with_position`, but takes the output of `lex_spanned` and also
+/// returns the span of every parsed form.
+pub fn parse_spanned(tokens: &[(Token, Span)]) -> Result<SpannedExpr, (usize, ParseError)> {
+    let (tokens, spans): (Vec<Token>, Vec<Span>) = tokens.iter().cloned().unzip();
+    parse_with_position(&tokens).map(|expr| attach_spans(expr, &spans, 0).0)
+}
+
+/// Pairs an already parsed expression with the spans of the tokens it was parsed from,
+/// starting at token `index`. Returns the index of the token after the expression.
+fn attach_spans(expr: Rc<Expr>, spans: &[Span], index: usize) -> (SpannedExpr, usize) {
+    match &*expr {
+        Expr::List(xs) => {
+            let mut children = Vec::new();
+            let mut next = index + 1;
+            for x in xs {
+                let (child, after) = attach_spans(x.clone(), spans, next);
+                children.push(child);
+                next = after;
+            }
+            let span = Span { start: spans[index].start, end: spans[next].end };
+            (SpannedExpr { expr, span, children }, next + 1)
+        },
+        _ => (SpannedExpr { expr, span: spans[index], children: Vec::new() }, index + 1),
+    }
+}
+
+/// Scans the whole token stream and reports every unmatched parenthesis, by token index
+/// and in order of position, rather than stopping at the first problem like `parse` does.
+pub fn check_balance(tokens: &[Token]) -> Vec<(usize, BalanceError)> {
+    let mut open = Vec::new();
+    let mut errors = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LPar => open.push(index),
+            Token::RPar if open.pop().is_none() => errors.push((index, BalanceError::Unexpected)),
+            _ => {},
+        }
+    }
+    errors.extend(open.into_iter().map(|index| (index, BalanceError::Unclosed)));
+    errors.sort_by_key(|(index, _)| *index);
+    errors
+}
 
+/// Parses a sequence of top-level forms.
+pub fn parse_program(tokens: &[Token]) -> Result<Vec<Rc<Expr>>, ParseError> {
+    let mut index = 0;
+    let mut exprs = Vec::new();
+    while index < tokens.len() {
+        match parser(tokens, index) {
+            ParseResult::Success(ix, expr) => {
+                exprs.push(expr);
+                index = ix;
+            },
+            ParseResult::Failure(_, err) => return Err(err),
+        }
     }
+    Ok(exprs)
 }
 
 fn parser(tokens: &[Token], index: usize) -> ParseResult {
+    let open = index;
     let mut index = index;
-    if let Some(mut x) = tokens.get(index) {
-        match &*x {
+    if let Some(x) = tokens.get(index) {
+        match x {
             Token::LPar => {
                 index += 1;
                 let mut exprs = Vec::new();
-                while *x != Token::RPar {
+                loop {
+                    match tokens.get(index) {
+                        None => return ParseResult::Failure(open, ParseError::Incomplete),
+                        Some(Token::RPar) => break,
+                        Some(_) => {},
+                    }
                     match parser(tokens, index) {
                         ParseResult::Success(ix, expr) => {
                             exprs.push(expr);
@@ -37,30 +147,26 @@ fn parser(tokens: &[Token], index: usize) -> ParseResult {
                         },
                         e => return e,
                     }
-                    if index >= tokens.len() {
-                        return ParseResult::Failure(ParseError::BadParse("Unclosed delimiter".into()))
-                    }
-                    x = &tokens[index];
                 }
 
                 ParseResult::Success(index + 1, Expr::list(&exprs))
             },
             Token::RPar => {
-                ParseResult::Failure(ParseError::BadParse("Unexpected ) encountered.".into()))
+                ParseResult::Failure(index, ParseError::BadParse("Unexpected ) encountered.".into()))
             },
+            Token::Str(s) => ParseResult::Success(index + 1, Expr::string(s)),
+            Token::Char(c) => ParseResult::Success(index + 1, Expr::character(*c)),
             Token::Literal(s) => {
                 if let Ok(n) = s.parse::<f64>() {
                     ParseResult::Success(index + 1, Expr::fnum(n))
                 } else {
-                    ParseResult::Success(index + 1, Expr::symbol(&s))
+                    ParseResult::Success(index + 1, Expr::symbol(s))
                 }
             },
-            _ => ParseResult::Failure(ParseError::BadParse(format!("Unknown token: {:?}", *x))),
-
         }
         
     } else {
-        ParseResult::Failure(ParseError::EOF)
+        ParseResult::Failure(index, ParseError::EOF)
     } 
 }
 
@@ -76,10 +182,10 @@ mod test {
             if let Expr::FNum(n) = *expr {
                 assert_eq!(n, 1.0);
             } else {
-                assert!(false, format!("expected fnum, got {:?}", *expr));
+                panic!("expected fnum, got {:?}", *expr);
             }
         } else {
-            assert!(false, format!("Expected success, failed with: {:?}", res));
+            panic!("Expected success, failed with: {:?}", res);
         }
     }
 
@@ -91,10 +197,10 @@ mod test {
             if let Expr::Symbol(s) = &*expr {
                 assert_eq!(s, "hello");
             } else {
-                assert!(false, format!("expected fnum, got {:?}", *expr));
+                panic!("expected fnum, got {:?}", *expr);
             }
         } else {
-            assert!(false, format!("Expected success, failed with: {:?}", res));
+            panic!("Expected success, failed with: {:?}", res);
         }
     }
 
@@ -117,7 +223,7 @@ mod test {
             assert_eq!(i, tokens.len());
             assert_eq!(expr, expected);
         } else {
-            assert!(false, format!("Expected success, failed with {:?}", res));
+            panic!("Expected success, failed with {:?}", res);
         }
     }
 
@@ -139,7 +245,7 @@ mod test {
             assert_eq!(i, tokens.len());
             assert_eq!(expected, expr);
         } else {
-            assert!(false, format!("Expected Success, failed with {:?}", res))
+            panic!("Expected Success, failed with {:?}", res)
         }
     }
 
@@ -172,7 +278,7 @@ mod test {
             assert_eq!(i, tokens.len());
             assert_eq!(expr, expected);
         } else {
-            assert!(false, format!("Expected Success, got {:?}", res)); 
+            panic!("Expected Success, got {:?}", res); 
         }
     }
 
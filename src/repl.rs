@@ -0,0 +1,135 @@
+use crate::eval::{eval, gen_print_output, operator_value, Environment, EvalResult};
+use crate::lex::{lex, LexError};
+use crate::parse::{parse_program, ParseError};
+use crate::types::Expr;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+/// Number of previous results bound as `_1`, `_2`, ...
+const HISTORY_LEN: usize = 5;
+
+const HELP: &str = "\
+:help   show this message
+:env    list the current bindings
+:reset  restore the default environment
+:quit   leave the REPL";
+
+/// Prompt shown while a form spans several lines.
+const CONTINUATION_PROMPT: &str = ".. ";
+
+/// An interactive session that keeps its environment between lines.
+#[derive(Default)]
+pub struct Repl {
+    pub env: Environment,
+    history: VecDeque<Rc<Expr>>,
+    /// Lines of a form that is not complete yet.
+    pending: String,
+}
+
+/// Whether `source` stops inside a string or an open list, so that more input could complete it.
+pub fn is_incomplete(source: &str) -> bool {
+    match lex(source) {
+        Err(LexError::UnterminatedString) => true,
+        Err(_) => false,
+        Ok(tokens) => matches!(parse_program(&tokens), Err(ParseError::Incomplete)),
+    }
+}
+
+impl Repl {
+    /// Evaluates every top-level form in `line` and returns the result of the last one.
+    pub fn eval_line(&mut self, line: &str) -> EvalResult {
+        let exprs = match lex(line) {
+            Err(e) => return EvalResult::Err(format!("Lex error: {:?}", e)),
+            Ok(tokens) => match parse_program(&tokens) {
+                Err(e) => return EvalResult::Err(format!("Parse error: {:?}", e)),
+                Ok(exprs) => exprs,
+            },
+        };
+        let mut result = EvalResult::Unit;
+        for expr in exprs {
+            result = eval(expr, &mut self.env);
+            match &result {
+                EvalResult::Expr(value) => self.record(value.clone()),
+                EvalResult::Err(_) => break,
+                EvalResult::Unit => {},
+            }
+        }
+        result
+    }
+
+    /// Binds `_` to the newest result and `_1`, `_2`, ... to the most recent ones.
+    fn record(&mut self, value: Rc<Expr>) {
+        self.history.push_front(value.clone());
+        self.history.truncate(HISTORY_LEN);
+        let _ = self.env.add_var("_", value);
+        for (i, v) in self.history.iter().enumerate() {
+            let _ = self.env.add_var(&format!("_{}", i + 1), v.clone());
+        }
+    }
+
+    /// Renders every visible binding as `name = value`, one per line. Unbound operators
+    /// are listed with the function value they read as.
+    pub fn env_listing(&mut self) -> String {
+        self.env.names().into_iter()
+            .map(|name| {
+                let value = if self.env.contains_key(&name) { Expr::symbol(&name) } else { operator_value(&name) };
+                format!("{} = {}", name, gen_print_output(value, &mut self.env))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Clears every binding and the result history.
+    pub fn reset(&mut self) {
+        self.env = Environment::default();
+        self.history.clear();
+        self.pending.clear();
+    }
+
+    /// Reads lines from `input` until it is exhausted or `:quit` is entered, writing printed output and results to `output`.
+    /// Lines starting with `:` are REPL commands rather than code. A form left open at the end
+    /// of a line is continued on the next ones and evaluated once it is complete.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let mut line = line?;
+            if !self.pending.is_empty() {
+                self.pending.push('\n');
+                self.pending.push_str(&line);
+                line = std::mem::take(&mut self.pending);
+            }
+            if is_incomplete(&line) {
+                self.pending = line;
+                write!(output, "{}", CONTINUATION_PROMPT)?;
+                output.flush()?;
+                continue;
+            }
+            if let Some(command) = line.trim().strip_prefix(':') {
+                match command {
+                    "help" => writeln!(output, "{}", HELP)?,
+                    "env" => writeln!(output, "{}", self.env_listing())?,
+                    "reset" => self.reset(),
+                    "quit" => return Ok(()),
+                    _ => writeln!(output, "Error: unknown command :{}, try :help", command)?,
+                }
+            } else if !line.trim().is_empty() {
+                self.env.output_buffers.push(String::new());
+                let result = self.eval_line(&line);
+                write!(output, "{}", self.env.output_buffers.pop().unwrap_or_default())?;
+                for warning in self.env.warnings.drain(..) {
+                    writeln!(output, "Warning: {}", warning)?;
+                }
+                match result {
+                    EvalResult::Expr(e) => writeln!(output, "{}", gen_print_output(e, &mut self.env))?,
+                    EvalResult::Err(e) => writeln!(output, "Error: {}", e)?,
+                    EvalResult::Unit => {},
+                }
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -1,19 +1,62 @@
+use std::cell::RefCell;
 use std::rc::Rc;
 
 
+/// Variables a function value closes over, by name. Copies of the value share them, so a
+/// `set!` of one of them in the function's body is seen by later calls.
+pub type Captured = Rc<RefCell<Vec<(String, Rc<Expr>)>>>;
+
 #[derive(Debug)]
 pub enum Expr {
     Symbol(String),
     FNum(f64),
+    Str(String),
+    Char(char),
     List(Vec<Rc<Expr>>),
+    /// A function value: its parameter names, body and the variables it closes over.
+    Lambda(Vec<String>, Rc<Expr>, Captured),
+    /// A cons cell: the head of a list and the list holding the rest of it.
+    /// The empty `List` terminates a chain of pairs.
+    Pair(Rc<Expr>, Rc<Expr>),
+    /// A delayed expression and, once forced, its cached value.
+    Promise(Rc<Expr>, RefCell<Option<Rc<Expr>>>),
+    /// A function together with arguments already supplied to it, which are passed
+    /// before the remaining ones when it is called.
+    Partial(Rc<Expr>, Vec<Rc<Expr>>),
+    /// Key-value entries in insertion order. No two keys are `=` to each other.
+    Map(Vec<(Rc<Expr>, Rc<Expr>)>),
+    /// Several results returned at once by `values`.
+    Values(Vec<Rc<Expr>>),
+}
+
+/// Numeric equality used by `=` and by comparisons of `Expr` values.
+///
+/// Numbers within 1e-8 of each other are equal, infinities equal themselves,
+/// `-0.0` equals `0.0`, and `NaN` equals nothing, not even itself, as in IEEE 754.
+pub fn float_eq(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= 1e-8
 }
 
 impl PartialEq for Expr {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Expr::Symbol(s1), Expr::Symbol(s2)) => s1 == s2,
-            (Expr::FNum(n1), Expr::FNum(n2)) => (n1 - n2).abs() <= 1e-8,
+            (Expr::FNum(n1), Expr::FNum(n2)) => float_eq(*n1, *n2),
+            (Expr::Str(s1), Expr::Str(s2)) => s1 == s2,
+            (Expr::Char(c1), Expr::Char(c2)) => c1 == c2,
             (Expr::List(xs1), Expr::List(xs2)) => xs1 == xs2,
+            (Expr::Lambda(ps1, b1, c1), Expr::Lambda(ps2, b2, c2)) => {
+                ps1 == ps2 && b1 == b2 && (Rc::ptr_eq(c1, c2) || *c1.borrow() == *c2.borrow())
+            },
+            (Expr::Pair(..), Expr::Pair(..))
+            | (Expr::Pair(..), Expr::List(_))
+            | (Expr::List(_), Expr::Pair(..)) => self.items() == other.items(),
+            (Expr::Partial(f1, xs1), Expr::Partial(f2, xs2)) => f1 == f2 && xs1 == xs2,
+            (Expr::Values(xs1), Expr::Values(xs2)) => xs1 == xs2,
+            (Expr::Map(es1), Expr::Map(es2)) => {
+                es1.len() == es2.len() && es1.iter().all(|(k, v)| es2.iter().any(|(k2, v2)| k == k2 && v == v2))
+            },
+            (Expr::Promise(..), Expr::Promise(..)) => std::ptr::eq(self, other),
             _ => false,
         }
     }
@@ -30,10 +73,177 @@ impl Expr {
         Rc::new(Expr::FNum(n))
     }
 
+    pub fn string(s: &str) -> Rc<Expr> {
+        Rc::new(Expr::Str(s.to_string()))
+    }
+
+    pub fn character(c: char) -> Rc<Expr> {
+        Rc::new(Expr::Char(c))
+    }
+
     pub fn list(xs: &[Rc<Expr>]) -> Rc<Expr> {
-        Rc::new(Expr::List(xs.iter().cloned().collect()))
+        Rc::new(Expr::List(xs.to_vec()))
+    }
+
+    pub fn lambda(params: &[String], body: Rc<Expr>) -> Rc<Expr> {
+        Expr::closure(params, body, &[])
+    }
+
+    pub fn closure(params: &[String], body: Rc<Expr>, captured: &[(String, Rc<Expr>)]) -> Rc<Expr> {
+        Rc::new(Expr::Lambda(params.to_vec(), body, Rc::new(RefCell::new(captured.to_vec()))))
+    }
+
+    pub fn cons(head: Rc<Expr>, tail: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Pair(head, tail))
+    }
+
+    pub fn promise(expr: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Promise(expr, RefCell::new(None)))
+    }
+
+    pub fn partial(f: Rc<Expr>, args: &[Rc<Expr>]) -> Rc<Expr> {
+        Rc::new(Expr::Partial(f, args.to_vec()))
+    }
+
+    pub fn values(xs: &[Rc<Expr>]) -> Rc<Expr> {
+        Rc::new(Expr::Values(xs.to_vec()))
+    }
+
+    /// Builds a map from `entries`. When keys repeat, the last value wins and keeps
+    /// the position of the key's first occurrence.
+    pub fn map(entries: &[(Rc<Expr>, Rc<Expr>)]) -> Rc<Expr> {
+        let mut unique: Vec<(Rc<Expr>, Rc<Expr>)> = Vec::new();
+        for (key, value) in entries {
+            match unique.iter_mut().find(|(k, _)| k == key) {
+                Some(entry) => entry.1 = value.clone(),
+                None => unique.push((key.clone(), value.clone())),
+            }
+        }
+        Rc::new(Expr::Map(unique))
+    }
+
+    /// Builds a chain of cons cells holding `xs`.
+    pub fn cons_list(xs: &[Rc<Expr>]) -> Rc<Expr> {
+        xs.iter().rev()
+            .fold(Expr::list(&[]), |tail, x| Expr::cons(x.clone(), tail))
+    }
+
+    /// Collects the elements of a list in either representation, or `None` if this is not a list.
+    pub fn items(&self) -> Option<Vec<Rc<Expr>>> {
+        match self {
+            Expr::List(xs) => Some(xs.clone()),
+            Expr::Pair(head, tail) => {
+                let mut xs = vec![head.clone()];
+                let mut rest = tail.clone();
+                loop {
+                    let next = match &*rest {
+                        Expr::Pair(h, t) => {
+                            xs.push(h.clone());
+                            t.clone()
+                        },
+                        Expr::List(ys) => {
+                            xs.extend(ys.iter().cloned());
+                            return Some(xs);
+                        },
+                        _ => return None,
+                    };
+                    rest = next;
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the first element of a non-empty list.
+    pub fn car(&self) -> Option<Rc<Expr>> {
+        match self {
+            Expr::List(xs) => xs.first().cloned(),
+            Expr::Pair(head, _) => Some(head.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns everything but the first element of a non-empty list.
+    ///
+    /// This is O(1) for cons cells but copies the tail of the vector form.
+    pub fn cdr(&self) -> Option<Rc<Expr>> {
+        match self {
+            Expr::List(xs) if !xs.is_empty() => Some(Expr::list(&xs[1..])),
+            Expr::Pair(_, tail) => Some(tail.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Counts every node in the expression tree, including the lists themselves.
+pub fn count_nodes(expr: &Expr) -> usize {
+    match expr {
+        Expr::List(xs) | Expr::Values(xs) => 1 + xs.iter().map(|x| count_nodes(x)).sum::<usize>(),
+        Expr::Pair(..) => 1 + expr.items().unwrap_or_default().iter().map(|x| count_nodes(x)).sum::<usize>(),
+        Expr::Lambda(_, body, _) => 1 + count_nodes(body),
+        Expr::Partial(f, args) => 1 + count_nodes(f) + args.iter().map(|x| count_nodes(x)).sum::<usize>(),
+        Expr::Map(entries) => 1 + entries.iter().map(|(k, v)| count_nodes(k) + count_nodes(v)).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+/// Extracts the numbers of a list, failing on the first element that is not a number.
+pub fn as_f64_vec(expr: &Expr) -> Result<Vec<f64>, String> {
+    expr.items()
+        .ok_or_else(|| format!("expected a list, got {:?}", expr))?
+        .iter()
+        .enumerate()
+        .map(|(i, x)| match &**x {
+            Expr::FNum(n) => Ok(*n),
+            _ => Err(format!("element {} is not a number, got {:?}", i, x)),
+        })
+        .collect()
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
     }
+    out.push('"');
+    out
+}
 
+fn json_items(xs: &[Rc<Expr>]) -> String {
+    let items: Vec<String> = xs.iter().map(|x| to_json(x)).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Serializes the expression tree as JSON. Every node is an object with a
+/// `type` field; lists hold their elements in `items`, leaves in `value`.
+pub fn to_json(expr: &Expr) -> String {
+    match expr {
+        Expr::Symbol(s) => format!("{{\"type\":\"symbol\",\"value\":{}}}", json_string(s)),
+        Expr::FNum(n) if n.is_finite() => format!("{{\"type\":\"number\",\"value\":{}}}", n),
+        Expr::FNum(_) => "{\"type\":\"number\",\"value\":null}".into(),
+        Expr::Str(s) => format!("{{\"type\":\"string\",\"value\":{}}}", json_string(s)),
+        Expr::Char(c) => format!("{{\"type\":\"char\",\"value\":{}}}", json_string(&c.to_string())),
+        Expr::List(xs) => format!("{{\"type\":\"list\",\"items\":{}}}", json_items(xs)),
+        Expr::Pair(..) => format!("{{\"type\":\"list\",\"items\":{}}}", json_items(&expr.items().unwrap_or_default())),
+        Expr::Lambda(params, body, _) => {
+            let ps: Vec<String> = params.iter().map(|p| json_string(p)).collect();
+            format!("{{\"type\":\"lambda\",\"params\":[{}],\"body\":{}}}", ps.join(","), to_json(body))
+        },
+        Expr::Promise(delayed, _) => format!("{{\"type\":\"promise\",\"expr\":{}}}", to_json(delayed)),
+        Expr::Partial(f, args) => format!("{{\"type\":\"partial\",\"fn\":{},\"args\":{}}}", to_json(f), json_items(args)),
+        Expr::Map(entries) => {
+            let es: Vec<String> = entries.iter().map(|(k, v)| format!("[{},{}]", to_json(k), to_json(v))).collect();
+            format!("{{\"type\":\"map\",\"entries\":[{}]}}", es.join(","))
+        },
+        Expr::Values(xs) => format!("{{\"type\":\"values\",\"items\":{}}}", json_items(xs)),
+    }
 }
 
 #[cfg(test)]
@@ -43,10 +253,10 @@ mod test {
     #[test]
     fn build_symbol() {
         let sym_str = "a";
-        let sym = Expr::symbol(&sym_str);
+        let sym = Expr::symbol(sym_str);
         match &*sym {
             Expr::Symbol(s) => assert_eq!(sym_str, s),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
@@ -56,7 +266,7 @@ mod test {
         let fnum = Expr::fnum(val);
         match &*fnum {
             Expr::FNum(n) => assert!((n - val).abs() <= 1e-8),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
@@ -76,7 +286,76 @@ mod test {
                 assert_eq!(Expr::fnum(1.0), xs[1]);
                 assert_eq!(Expr::fnum(1.0), xs[2]);
             },
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
+
+    #[test]
+    fn cons_list_matches_vector_form() {
+        let xs = [Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(3.0)];
+        let pairs = Expr::cons_list(&xs);
+        assert_eq!(Expr::list(&xs), pairs);
+        assert_eq!(Some(xs.to_vec()), pairs.items());
+    }
+
+    #[test]
+    fn cdr_of_pair_shares_tail() {
+        let tail = Expr::cons_list(&[Expr::fnum(2.0)]);
+        let pair = Expr::cons(Expr::fnum(1.0), tail.clone());
+        assert_eq!(Some(Expr::fnum(1.0)), pair.car());
+        assert!(Rc::ptr_eq(&tail, &pair.cdr().unwrap()));
+    }
+
+    #[test]
+    fn count_nodes_of_leaf() {
+        assert_eq!(1, count_nodes(&Expr::fnum(1.0)));
+    }
+
+    #[test]
+    fn count_nodes_of_flat_list() {
+        let e = Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]);
+        assert_eq!(4, count_nodes(&e));
+    }
+
+    #[test]
+    fn count_nodes_of_nested_list() {
+        // (+ 1 (* 2 3))
+        let e = Expr::list(&[
+            Expr::symbol("+"),
+            Expr::fnum(1.0),
+            Expr::list(&[Expr::symbol("*"), Expr::fnum(2.0), Expr::fnum(3.0)]),
+        ]);
+        assert_eq!(7, count_nodes(&e));
+    }
+
+    #[test]
+    fn float_eq_special_values() {
+        assert!(!float_eq(f64::NAN, f64::NAN));
+        assert!(float_eq(f64::INFINITY, f64::INFINITY));
+        assert!(!float_eq(f64::INFINITY, f64::NEG_INFINITY));
+        assert!(float_eq(-0.0, 0.0));
+    }
+
+    #[test]
+    fn as_f64_vec_of_numbers() {
+        let e = Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.5)]);
+        assert_eq!(Ok(vec![1.0, 2.5]), as_f64_vec(&e));
+        assert_eq!(Ok(vec![]), as_f64_vec(&Expr::list(&[])));
+    }
+
+    #[test]
+    fn as_f64_vec_names_first_non_number() {
+        let e = Expr::list(&[Expr::fnum(1.0), Expr::symbol("a"), Expr::string("b")]);
+        assert_eq!(Err("element 1 is not a number, got Symbol(\"a\")".to_string()), as_f64_vec(&e));
+        assert!(as_f64_vec(&Expr::FNum(1.0)).is_err());
+    }
+
+    #[test]
+    fn maps_compare_regardless_of_order() {
+        let (a, b) = (Expr::symbol("a"), Expr::symbol("b"));
+        let m1 = Expr::map(&[(a.clone(), Expr::fnum(1.0)), (b.clone(), Expr::fnum(2.0))]);
+        let m2 = Expr::map(&[(b.clone(), Expr::fnum(2.0)), (a.clone(), Expr::fnum(1.0))]);
+        assert_eq!(m1, m2);
+        assert_ne!(m1, Expr::map(&[(a, Expr::fnum(1.0))]));
+    }
 }
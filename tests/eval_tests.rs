@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod eval_tests {
-    use mlisp::eval::{eval, gen_print_output, Environment, EvalResult};
+    use mlisp::eval::{eval, gen_print_output, pretty, Environment, EvalResult};
     use mlisp::types::Expr;
 
     #[test]
@@ -11,9 +11,9 @@ mod eval_tests {
         let mut env = Environment::empty();
         env.push_context();
 
-        // Step 1: evaluate expr
+        // Step 1: evaluate expr, which returns the bound value
         let r1 = eval(vals, &mut env);
-        assert_eq!(r1, EvalResult::Unit);
+        assert_eq!(r1, EvalResult::Expr(Expr::fnum(expected)));
 
         // Step 2: check x = 5 is in environment
         let lookup = Expr::symbol("x");
@@ -66,9 +66,9 @@ mod eval_tests {
         let mut env = Environment::empty();
         env.push_context();
 
-        // Step 1: evaluate expr
+        // Step 1: evaluate expr, which returns the bound value
         let r1 = eval(vals, &mut env);
-        assert_eq!(r1, EvalResult::Unit);
+        assert_eq!(r1, EvalResult::Expr(Expr::fnum(expected)));
 
         // Step 2: check x = 5 is in environment
         let lookup = Expr::symbol("x");
@@ -380,14 +380,14 @@ mod eval_tests {
     fn add_fn_to_env() {
         let x1_sym = "x1";
         let x2_sym = "x2";
-        let x1 = Expr::symbol(&x1_sym);
-        let x2 = Expr::symbol(&x2_sym);
+        let x1 = Expr::symbol(x1_sym);
+        let x2 = Expr::symbol(x2_sym);
         let params = Expr::list(&[x1.clone(), x2.clone()]);
         let fn_body = Expr::list(&[Expr::symbol("+"), x1.clone(), x2.clone()]);
         let f_name = "test-func";
         let expr = Expr::list(&[
             Expr::symbol("fn"),
-            Expr::symbol(&f_name),
+            Expr::symbol(f_name),
             params.clone(),
             fn_body.clone(),
         ]);
@@ -397,8 +397,8 @@ mod eval_tests {
         let r = eval(expr, &mut env);
         assert_eq!(r, EvalResult::Unit);
 
-        env.lookup(&f_name).map_or_else(
-            || assert!(false, "Expected function in environment but got None"),
+        env.lookup(f_name).map_or_else(
+            || panic!("Expected function in environment but got None"),
             |(params, body)| {
                 assert_eq!(&params[0], x1_sym);
                 assert_eq!(&params[1], x2_sym);
@@ -440,10 +440,10 @@ mod eval_tests {
             if let Expr::FNum(n) = *e {
                 assert_eq!(n, x + y + 1.0);
             } else {
-                assert!(false, format!("Expected FNum(6.0), got {:?}", e));
+                panic!("Expected FNum(6.0), got {:?}", e);
             }
         } else {
-            assert!(false, format!("Expected Expr::fnum(6.0), got {:?}", r2));
+            panic!("Expected Expr::fnum(6.0), got {:?}", r2);
         }
     }
 
@@ -459,9 +459,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e, &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("x".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("x")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -476,9 +476,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e.clone(), &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("x".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("x")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -494,9 +494,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e.clone(), &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("y".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("y")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -519,21 +519,21 @@ mod eval_tests {
             Expr::symbol("body"),
         )
         .map_or_else(
-            |e| assert!(false, format!("got error {}", e)),
+            |e| panic!("got error {}", e),
             |_| {
                 assert_eq!(
-                    "<func-object: test-func>",
+                    "<func-object: test-func (x1 x2)>",
                     gen_print_output(Expr::symbol("test-func"), &mut env)
                 )
             },
         );
         let _ = env
             .add_var("x", Expr::fnum(42.0))
-            .map_err(|e| assert!(false, format!("got error {}", e)));
+            .map_err(|e| panic!("got error {}", e));
 
         let e4 = Expr::list(&[Expr::symbol("test-func"), Expr::symbol("x"), e3.clone()]);
         assert_eq!(
-            "(<func-object: test-func> 42 (hello world))",
+            "(<func-object: test-func (x1 x2)> 42 (hello world))",
             gen_print_output(e4.clone(), &mut env)
         );
 
@@ -555,6 +555,392 @@ mod eval_tests {
         eval(e6.clone(), &mut env);
     }
 
+    #[test]
+    fn eval_quoted_arithmetic() {
+        // (eval (quote (+ 1 2)))
+        let e = Expr::list(&[
+            Expr::symbol("eval"),
+            Expr::list(&[
+                Expr::symbol("quote"),
+                Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]),
+            ]),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), eval(e, &mut env));
+    }
+
+    #[test]
+    fn eval_quoted_bound_symbol() {
+        // (eval (quote x)) with x bound to 7
+        let e = Expr::list(&[
+            Expr::symbol("eval"),
+            Expr::list(&[Expr::symbol("quote"), Expr::symbol("x")]),
+        ]);
+        let mut env = Environment::from_vars(&[("x", Expr::fnum(7.0))]);
+        assert_eq!(EvalResult::Expr(Expr::fnum(7.0)), eval(e, &mut env));
+    }
+
+    #[test]
+    fn map_named_function_by_reference() {
+        // (fn add-1 (x) (+ x 1))
+        let fn_def = Expr::list(&[
+            Expr::symbol("fn"),
+            Expr::symbol("add-1"),
+            Expr::list(&[Expr::symbol("x")]),
+            Expr::list(&[Expr::symbol("+"), Expr::symbol("x"), Expr::fnum(1.0)]),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Unit, eval(fn_def, &mut env));
+
+        // (map (function add-1) (quote (1 2 3)))
+        let e = Expr::list(&[
+            Expr::symbol("map"),
+            Expr::list(&[Expr::symbol("function"), Expr::symbol("add-1")]),
+            Expr::list(&[
+                Expr::symbol("quote"),
+                Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(3.0)]),
+            ]),
+        ]);
+        let expected = Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0), Expr::fnum(4.0)]);
+        assert_eq!(EvalResult::Expr(expected), eval(e, &mut env));
+    }
+
+    #[test]
+    fn user_defined_operator_shadows_builtin() {
+        let add = Expr::list(&[Expr::symbol("+"), Expr::fnum(2.0), Expr::fnum(3.0)]);
+        let mut env = Environment::default();
+        env.push_context();
+
+        // (fn + (a b) (* a b))
+        let fn_def = Expr::list(&[
+            Expr::symbol("fn"),
+            Expr::symbol("+"),
+            Expr::list(&[Expr::symbol("a"), Expr::symbol("b")]),
+            Expr::list(&[Expr::symbol("*"), Expr::symbol("a"), Expr::symbol("b")]),
+        ]);
+        assert_eq!(EvalResult::Unit, eval(fn_def, &mut env));
+        assert_eq!(EvalResult::Expr(Expr::fnum(6.0)), eval(add.clone(), &mut env));
+
+        // Once the context is gone the builtin is used again.
+        env.pop_context();
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), eval(add, &mut env));
+    }
+
+    #[test]
+    fn cons_and_cdr_builtins() {
+        // (cons 0 (cdr (quote (1 2 3))))
+        let e = Expr::list(&[
+            Expr::symbol("cons"),
+            Expr::fnum(0.0),
+            Expr::list(&[
+                Expr::symbol("cdr"),
+                Expr::list(&[
+                    Expr::symbol("quote"),
+                    Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(3.0)]),
+                ]),
+            ]),
+        ]);
+        let mut env = Environment::default();
+        let expected = Expr::list(&[Expr::fnum(0.0), Expr::fnum(2.0), Expr::fnum(3.0)]);
+        match eval(e, &mut env) {
+            EvalResult::Expr(expr) => {
+                assert_eq!(expected, expr);
+                assert_eq!("(0 2 3)", gen_print_output(expr, &mut env));
+            },
+            r => panic!("Expected expression, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn car_and_cons_share_a_chain_of_pairs() {
+        let head = Expr::fnum(1.0);
+        let xs = Expr::cons_list(&[head.clone(), Expr::fnum(2.0), Expr::fnum(3.0)]);
+        let mut env = Environment::from_vars(&[("xs", xs.clone())]);
+        match eval(Expr::list(&[Expr::symbol("car"), Expr::symbol("xs")]), &mut env) {
+            EvalResult::Expr(x) => assert!(std::rc::Rc::ptr_eq(&head, &x)),
+            r => panic!("Expected expression, got {:?}", r),
+        }
+        // (cdr (cons 0 xs))
+        let e = Expr::list(&[
+            Expr::symbol("cdr"),
+            Expr::list(&[Expr::symbol("cons"), Expr::fnum(0.0), Expr::symbol("xs")]),
+        ]);
+        match eval(e, &mut env) {
+            EvalResult::Expr(tail) => assert!(std::rc::Rc::ptr_eq(&xs, &tail)),
+            r => panic!("Expected expression, got {:?}", r),
+        }
+        let e = Expr::list(&[Expr::symbol("cons"), Expr::fnum(0.0), Expr::fnum(2.0)]);
+        assert_eq!(EvalResult::Err("cons: expected a list, got FNum(2.0)".into()), eval(e, &mut env));
+    }
+
+    #[test]
+    fn with_output_captures_print() {
+        // (with-output (print "hi"))
+        let e = Expr::list(&[
+            Expr::symbol("with-output"),
+            Expr::list(&[Expr::symbol("print"), Expr::string("hi")]),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::string("hi")), eval(e, &mut env));
+        assert!(env.output_buffers.is_empty());
+    }
+
+    #[test]
+    fn numeric_parameter_name_is_reported() {
+        // (fn f (x 3) x)
+        let e = Expr::list(&[
+            Expr::symbol("fn"),
+            Expr::symbol("f"),
+            Expr::list(&[Expr::symbol("x"), Expr::fnum(3.0)]),
+            Expr::symbol("x"),
+        ]);
+        let mut env = Environment::default();
+        match eval(e, &mut env) {
+            EvalResult::Err(msg) => assert_eq!("function parameter must be a symbol, got FNum(3.0)", msg),
+            r => panic!("Expected error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn numeric_variable_name_is_reported() {
+        // (let 3 5)
+        let e = Expr::list(&[Expr::symbol("let"), Expr::fnum(3.0), Expr::fnum(5.0)]);
+        let mut env = Environment::default();
+        match eval(e, &mut env) {
+            EvalResult::Err(msg) => assert!(msg.contains("FNum(3.0)"), "unexpected message: {}", msg),
+            r => panic!("Expected error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn print_fails_once_output_limit_is_exceeded() {
+        // (print "abc") writes four bytes including the newline.
+        let e = Expr::list(&[Expr::symbol("print"), Expr::string("abc")]);
+        let mut env = Environment {
+            output_limit: Some(10),
+            ..Environment::default()
+        };
+        env.output_buffers.push(String::new());
+
+        assert_eq!(EvalResult::Unit, eval(e.clone(), &mut env));
+        assert_eq!(EvalResult::Unit, eval(e.clone(), &mut env));
+        assert_eq!(EvalResult::Err("output limit exceeded".into()), eval(e, &mut env));
+        assert_eq!(Some(&"abc\nabc\n".to_string()), env.output_buffers.last());
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        let seed = Expr::list(&[Expr::symbol("seed!"), Expr::fnum(42.0)]);
+        let float = Expr::list(&[Expr::symbol("random")]);
+        let int = Expr::list(&[Expr::symbol("random"), Expr::fnum(10.0)]);
+        let mut env = Environment::default();
+
+        let draw = |env: &mut Environment| {
+            assert_eq!(EvalResult::Unit, eval(seed.clone(), env));
+            (eval(float.clone(), env), eval(int.clone(), env))
+        };
+        let first = draw(&mut env);
+        let second = draw(&mut env);
+        assert_eq!(first, second);
+
+        match first {
+            (EvalResult::Expr(f), EvalResult::Expr(i)) => match (&*f, &*i) {
+                (Expr::FNum(f), Expr::FNum(i)) => {
+                    assert!((0.0..1.0).contains(f));
+                    assert!((0.0..10.0).contains(i) && i.fract() == 0.0);
+                },
+                r => panic!("Expected numbers, got {:?}", r),
+            },
+            r => panic!("Expected expressions, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn infinite_loop_hits_step_limit() {
+        // (while True (+ 1 1))
+        let e = Expr::list(&[
+            Expr::symbol("while"),
+            Expr::symbol("True"),
+            Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(1.0)]),
+        ]);
+        let mut env = Environment {
+            step_limit: Some(1000),
+            ..Environment::default()
+        };
+        assert_eq!(EvalResult::Err("step limit exceeded".into()), eval(e, &mut env));
+    }
+
+    #[test]
+    fn if_treats_false_symbol_as_falsey() {
+        // (if (= 1 2) x y)
+        let e = Expr::list(&[
+            Expr::symbol("if"),
+            Expr::list(&[Expr::symbol("="), Expr::fnum(1.0), Expr::fnum(2.0)]),
+            Expr::symbol("x"),
+            Expr::symbol("y"),
+        ]);
+        let mut env = Environment::from_vars(&[("x", Expr::fnum(1.0)), ("y", Expr::fnum(2.0))]);
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), eval(e, &mut env));
+    }
+
+    #[test]
+    fn read_eval_string_shares_step_limit() {
+        // (read-eval-string "(while True (+ 1 1))")
+        let e = Expr::list(&[Expr::symbol("read-eval-string"), Expr::string("(while True (+ 1 1))")]);
+        let mut env = Environment {
+            step_limit: Some(1000),
+            ..Environment::default()
+        };
+        assert_eq!(EvalResult::Err("step limit exceeded".into()), eval(e, &mut env));
+    }
+
+    #[test]
+    fn while_stops_when_condition_is_falsey() {
+        // (while (!= x 3) (let x (+ x 1)))
+        let e = Expr::list(&[
+            Expr::symbol("while"),
+            Expr::list(&[Expr::symbol("!="), Expr::symbol("x"), Expr::fnum(3.0)]),
+            Expr::list(&[
+                Expr::symbol("let"),
+                Expr::symbol("x"),
+                Expr::list(&[Expr::symbol("+"), Expr::symbol("x"), Expr::fnum(1.0)]),
+            ]),
+        ]);
+        let mut env = Environment::from_vars(&[("x", Expr::fnum(0.0))]);
+        assert_eq!(EvalResult::Unit, eval(e, &mut env));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), eval(Expr::symbol("x"), &mut env));
+    }
+
+    #[test]
+    fn let_in_expression_position() {
+        // (+ (let x 5) 1)
+        let e = Expr::list(&[
+            Expr::symbol("+"),
+            Expr::list(&[Expr::symbol("let"), Expr::symbol("x"), Expr::fnum(5.0)]),
+            Expr::fnum(1.0),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::fnum(6.0)), eval(e, &mut env));
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), eval(Expr::symbol("x"), &mut env));
+    }
+
+    #[test]
+    fn printed_function_value_shows_parameters() {
+        // (fn pair (x y) (+ x y))
+        let fn_def = Expr::list(&[
+            Expr::symbol("fn"),
+            Expr::symbol("pair"),
+            Expr::list(&[Expr::symbol("x"), Expr::symbol("y")]),
+            Expr::list(&[Expr::symbol("+"), Expr::symbol("x"), Expr::symbol("y")]),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Unit, eval(fn_def, &mut env));
+
+        let f = eval(Expr::list(&[Expr::symbol("function"), Expr::symbol("pair")]), &mut env);
+        match f {
+            EvalResult::Expr(f) => assert_eq!("<func-object: lambda (x y)>", gen_print_output(f, &mut env)),
+            r => panic!("Expected function value, got {:?}", r),
+        }
+        assert_eq!("<func-object: pair (x y)>", gen_print_output(Expr::symbol("pair"), &mut env));
+    }
+
+    #[test]
+    fn chained_comparison_increasing() {
+        let e = Expr::list(&[
+            Expr::symbol("<"),
+            Expr::fnum(1.0),
+            Expr::fnum(2.0),
+            Expr::fnum(3.0),
+            Expr::fnum(4.0),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), eval(e, &mut env));
+    }
+
+    #[test]
+    fn chained_comparison_out_of_order_pair() {
+        // (< 1 3 2 4): 3 < 2 is false
+        let e = Expr::list(&[
+            Expr::symbol("<"),
+            Expr::fnum(1.0),
+            Expr::fnum(3.0),
+            Expr::fnum(2.0),
+            Expr::fnum(4.0),
+        ]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), eval(e, &mut env));
+    }
+
+    #[test]
+    fn not_without_arguments_is_an_error() {
+        let e = Expr::list(&[Expr::symbol("not")]);
+        let mut env = Environment::default();
+        match eval(e, &mut env) {
+            EvalResult::Err(msg) => assert_eq!("not takes exactly one argument, got 0", msg),
+            r => panic!("Expected error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn not_of_empty_list_does_not_panic() {
+        let e = Expr::list(&[Expr::symbol("not"), Expr::list(&[])]);
+        let mut env = Environment::default();
+        assert!(matches!(eval(e, &mut env), EvalResult::Expr(_)));
+    }
+
+    #[test]
+    fn not_negates_the_value_of_its_argument() {
+        let comparison = Expr::list(&[Expr::symbol(">"), Expr::fnum(1.0), Expr::fnum(2.0)]);
+        let e = Expr::list(&[Expr::symbol("not"), comparison]);
+        let mut env = Environment::default();
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), eval(e.clone(), &mut env));
+        let branch = Expr::list(&[Expr::symbol("if"), e, Expr::fnum(1.0), Expr::fnum(2.0)]);
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), eval(branch, &mut env));
+    }
+
+    #[test]
+    fn and_or_without_arguments_are_errors() {
+        let mut env = Environment::default();
+        assert_eq!(
+            EvalResult::Err("and takes at least one argument".into()),
+            eval(Expr::list(&[Expr::symbol("and")]), &mut env)
+        );
+        assert_eq!(
+            EvalResult::Err("or takes at least one argument".into()),
+            eval(Expr::list(&[Expr::symbol("or")]), &mut env)
+        );
+    }
+
+    #[test]
+    fn pretty_indents_nested_lists_past_width() {
+        let inner = Expr::list(&[Expr::symbol("alpha"), Expr::symbol("beta"), Expr::symbol("gamma")]);
+        let e = Expr::list(&[Expr::symbol("f"), Expr::list(&[Expr::symbol("g"), inner]), Expr::fnum(1.0)]);
+        assert_eq!("(f (g (alpha beta gamma)) 1)", pretty(&e, 80));
+        assert_eq!("(f\n (g\n  (alpha\n   beta\n   gamma))\n 1)", pretty(&e, 10));
+    }
+
+    #[test]
+    fn trace_shows_inner_call_before_outer() {
+        // (+ 1 (* 2 3))
+        let e = Expr::list(&[
+            Expr::symbol("+"),
+            Expr::fnum(1.0),
+            Expr::list(&[Expr::symbol("*"), Expr::fnum(2.0), Expr::fnum(3.0)]),
+        ]);
+        let mut env = Environment {
+            trace: true,
+            ..Environment::default()
+        };
+        env.output_buffers.push(String::new());
+        assert_eq!(EvalResult::Expr(Expr::fnum(7.0)), eval(e, &mut env));
+
+        let log = env.output_buffers.pop().unwrap();
+        assert!(log.starts_with("[0] (+ 1 (* 2 3))\n"), "{}", log);
+        assert!(log.ends_with("[0] => 7\n"), "{}", log);
+        let multiplied = log.find("  [1] => 6\n").expect("inner multiply traced");
+        let added = log.find("[0] => 7\n").unwrap();
+        assert!(multiplied < added, "{}", log);
+    }
 }
 
 #[cfg(test)]
@@ -569,10 +955,7 @@ mod environment_tests {
         let mut env = Environment::empty();
 
         let r = env.add_var("a", Expr::fnum(1.0));
-        assert!(
-            r.is_err(),
-            format!("Expected add_var to fail, but it succeeded: {:?}", r)
-        );
+        assert!(r.is_err(), "Expected add_var to fail, but it succeeded: {:?}", r);
     }
 
     #[test]
@@ -596,14 +979,14 @@ mod environment_tests {
     fn default_environment_is_correct() {
         let env = Environment::default();
         env.lookup("False").map_or_else(
-            || assert!(false, "Expected Some, got None"),
+            || panic!("Expected Some, got None"),
             |(ps, expr)| {
                 assert_eq!(0, ps.len());
                 assert_eq!(Expr::list(&[]), expr);
             },
         );
         env.lookup("True").map_or_else(
-            || assert!(false, "Expected Some, got None"),
+            || panic!("Expected Some, got None"),
             |(ps, expr)| {
                 assert_eq!(0, ps.len());
                 assert_eq!(Expr::list(&[Expr::fnum(1.0)]), expr);
@@ -611,17 +994,26 @@ mod environment_tests {
         );
     }
 
+    #[test]
+    fn default_trait_gives_default_bindings() {
+        let env: Environment = Default::default();
+        assert_eq!(1, env.num_contexts());
+        assert!(env.contains_key("True"), "Default environment should contain True.");
+        assert!(env.contains_key("False"), "Default environment should contain False.");
+    }
+
     #[test]
     fn lookup_works() {
-        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0)))]
+        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0), false))]
             .iter()
             .cloned()
-            .collect::<HashMap<String, (Vec<String>, Rc<Expr>)>>();
+            .collect::<HashMap<String, (Vec<String>, Rc<Expr>, bool)>>();
         let env = Environment {
             contexts: vec![ctx],
+            ..Environment::empty()
         };
         env.lookup("x").map_or_else(
-            || assert!(false, "Expected Some, got None"),
+            || panic!("Expected Some, got None"),
             |(ps, expr)| {
                 assert_eq!(0, ps.len());
                 assert_eq!(Expr::fnum(1.0), expr);
@@ -632,12 +1024,13 @@ mod environment_tests {
 
     #[test]
     fn contain_key_works() {
-        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0)))]
+        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0), false))]
             .iter()
             .cloned()
-            .collect::<HashMap<String, (Vec<String>, Rc<Expr>)>>();
+            .collect::<HashMap<String, (Vec<String>, Rc<Expr>, bool)>>();
         let env = Environment {
             contexts: vec![ctx],
+            ..Environment::empty()
         };
         assert!(env.contains_key("x"), "Environment should contain x.");
         assert!(
@@ -660,7 +1053,7 @@ mod environment_tests {
 
         // Lookup the variable and validate
         env.lookup("a").map_or_else(
-            || assert!(false, "Failed to find var in environment."),
+            || panic!("Failed to find var in environment."),
             |(args, x)| {
                 assert_eq!(val, x);
                 assert_eq!(0usize, args.len());
@@ -669,8 +1062,9 @@ mod environment_tests {
 
         // Pop context and check variable is gone
         env.pop_context();
-        env.lookup("a")
-            .map(|x| assert!(false, format!("Expected Err, got {:?}", x)));
+        if let Some(x) = env.lookup("a") {
+            panic!("Expected Err, got {:?}", x);
+        }
         assert_eq!(0usize, env.num_contexts());
     }
 
@@ -678,8 +1072,8 @@ mod environment_tests {
     fn add_fn_to_context_works() {
         let x1_sym = "x1";
         let x2_sym = "x2";
-        let x1 = Expr::symbol(&x1_sym);
-        let x2 = Expr::symbol(&x2_sym);
+        let x1 = Expr::symbol(x1_sym);
+        let x2 = Expr::symbol(x2_sym);
         let fn_body = Expr::list(&[Expr::symbol("+"), x1.clone(), x2.clone()]);
         let f_name = "test-func";
 
@@ -687,13 +1081,13 @@ mod environment_tests {
         env.push_context();
         assert_eq!(1, env.contexts.len());
         let _ = env.add_fn(
-            &f_name,
+            f_name,
             &[x1_sym.to_string(), x2_sym.to_string()],
             fn_body.clone(),
         );
 
-        let _ = env.lookup(&f_name).map_or_else(
-            || assert!(false, "Expected function in environment but got None"),
+        env.lookup(f_name).map_or_else(
+            || panic!("Expected function in environment but got None"),
             |(params, body)| {
                 assert_eq!(&params[0], x1_sym);
                 assert_eq!(&params[1], x2_sym);
@@ -701,4 +1095,82 @@ mod environment_tests {
             },
         );
     }
+
+    #[test]
+    fn set_var_replaces_the_rc_instead_of_mutating_it() {
+        let shared = Expr::list(&[Expr::fnum(1.0)]);
+        let mut env = Environment::from_vars(&[("a", shared.clone()), ("b", shared.clone())]);
+        env.push_context();
+        assert!(env.set_var("a", Expr::fnum(2.0)).is_ok());
+
+        assert_eq!(Some(Expr::fnum(2.0)), env.lookup("a").map(|(_, e)| e));
+        let b = env.lookup("b").map(|(_, e)| e).unwrap();
+        assert!(Rc::ptr_eq(&shared, &b));
+        assert_eq!(Expr::list(&[Expr::fnum(1.0)]), shared);
+    }
+
+    #[test]
+    fn case_insensitive_names_collide() {
+        let mut env = Environment::default().ignore_case();
+        assert!(env.add_var("Foo", Expr::fnum(1.0)).is_ok());
+        assert!(env.add_var("foo", Expr::fnum(2.0)).is_ok());
+        assert_eq!(Some(Expr::fnum(2.0)), env.lookup("FOO").map(|(_, e)| e));
+        assert!(env.contains_key("fOO"));
+    }
+
+    #[test]
+    fn default_names_are_case_sensitive() {
+        let mut env = Environment::default();
+        assert!(env.add_var("Foo", Expr::fnum(1.0)).is_ok());
+        assert!(env.add_var("foo", Expr::fnum(2.0)).is_ok());
+        assert_eq!(Some(Expr::fnum(1.0)), env.lookup("Foo").map(|(_, e)| e));
+        assert!(!env.contains_key("FOO"));
+    }
+
+    #[test]
+    fn compact_releases_capacity_of_popped_contexts() {
+        let mut env = Environment::default();
+        for _ in 0..1000 {
+            env.push_context();
+        }
+        for _ in 0..1000 {
+            env.pop_context();
+        }
+        let grown = env.contexts.capacity();
+        assert!(grown >= 1001);
+
+        let reclaimed = env.compact();
+        assert!(env.contexts.capacity() < grown);
+        assert!(reclaimed >= grown - env.contexts.capacity());
+        assert_eq!(1, env.num_contexts());
+        assert!(env.contains_key("True"));
+        assert_eq!(0, env.compact());
+    }
+
+    #[test]
+    fn remove_var_uncovers_outer_binding() {
+        let mut env = Environment::default();
+        let _ = env.add_var("x", Expr::fnum(1.0));
+        env.push_context();
+        let _ = env.add_var("x", Expr::fnum(2.0));
+
+        assert_eq!(Ok((Vec::new(), Expr::fnum(2.0))), env.remove_var("x"));
+        assert_eq!(Some((Vec::new(), Expr::fnum(1.0))), env.lookup("x"));
+        assert!(env.remove_var("x").is_ok());
+        assert_eq!(None, env.lookup("x"));
+        assert!(env.remove_var("x").is_err());
+    }
+
+    #[test]
+    fn popped_var_binding_does_not_make_later_binding_mutable() {
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        env.push_context();
+        let _ = env.add_mutable_var("x", Expr::fnum(1.0));
+        assert!(env.set_var("x", Expr::fnum(2.0)).is_ok());
+        env.pop_context();
+
+        env.push_context();
+        let _ = env.add_var("x", Expr::fnum(1.0));
+        assert!(env.set_var("x", Expr::fnum(2.0)).is_err());
+    }
 }
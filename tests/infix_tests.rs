@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod infix_tests {
+    use mlisp::infix::{default_operators, rewrite_infix, Assoc, Operator};
+    use mlisp::lex::lex;
+    use mlisp::parse::{parse, ParseError};
+    use mlisp::types::Expr;
+    use std::rc::Rc;
+
+    fn read(source: &str) -> Result<Rc<Expr>, ParseError> {
+        let tokens = rewrite_infix(&lex(source).unwrap(), &default_operators())?;
+        parse(&tokens)
+    }
+
+    fn call(op: &str, a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        Expr::list(&[Expr::symbol(op), a, b])
+    }
+
+    #[test]
+    fn multiplication_binds_tighter() {
+        let expected = call("+", Expr::fnum(1.0), call("*", Expr::fnum(2.0), Expr::fnum(3.0)));
+        assert_eq!(expected, read("[1 + 2 * 3]").unwrap());
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let expected = call("*", call("+", Expr::fnum(1.0), Expr::fnum(2.0)), Expr::fnum(3.0));
+        assert_eq!(expected, read("[(1 + 2) * 3]").unwrap());
+        assert_eq!(expected, read("[[1 + 2] * 3]").unwrap());
+    }
+
+    #[test]
+    fn associativity() {
+        let expected = call("-", call("-", Expr::fnum(1.0), Expr::fnum(2.0)), Expr::fnum(3.0));
+        assert_eq!(expected, read("[1 - 2 - 3]").unwrap());
+        let expected = call("expt", Expr::fnum(2.0), call("expt", Expr::fnum(3.0), Expr::fnum(2.0)));
+        assert_eq!(expected, read("[2 ^ 3 ^ 2]").unwrap());
+    }
+
+    #[test]
+    fn prefix_syntax_is_unaffected() {
+        let expected = Expr::list(&[
+            Expr::symbol("print"),
+            call("+", Expr::symbol("x"), Expr::fnum(1.0)),
+        ]);
+        assert_eq!(expected, read("(print [x + 1])").unwrap());
+        assert_eq!(call("*", Expr::fnum(2.0), Expr::fnum(3.0)), read("(* 2 3)").unwrap());
+    }
+
+    #[test]
+    fn custom_precedence_table() {
+        let mut operators = default_operators();
+        operators.insert("*".into(), Operator { function: "*".into(), precedence: 0, assoc: Assoc::Left });
+        let tokens = rewrite_infix(&lex("[1 + 2 * 3]").unwrap(), &operators).unwrap();
+        let expected = call("*", call("+", Expr::fnum(1.0), Expr::fnum(2.0)), Expr::fnum(3.0));
+        assert_eq!(expected, parse(&tokens).unwrap());
+    }
+
+    #[test]
+    fn malformed_infix_expressions() {
+        assert!(matches!(read("[1 +"), Err(ParseError::Incomplete)));
+        assert!(matches!(read("[1 + * 2]"), Err(ParseError::BadParse(_))));
+        assert!(matches!(read("[1 2]"), Err(ParseError::BadParse(_))));
+        assert!(matches!(read("1 ]"), Err(ParseError::BadParse(_))));
+    }
+}
@@ -1,13 +1,14 @@
 #[cfg(test)]
 mod interpreter_tests {
-    use mlisp::eval::EvalResult;
-    use mlisp::interpreter::run_interpreter;
+    use mlisp::eval::{Environment, EvalResult, SPECIAL_FORMS};
+    use mlisp::interpreter::{run_interpreter, run_interpreter_ast, run_interpreter_with_env, run_interpreter_with_timeout};
+    use std::time::{Duration, Instant};
     use mlisp::types::Expr;
 
     #[test]
     fn simple_statement() {
         let program = "(+ 1 (- 3 2))";
-        let r = run_interpreter(&program);
+        let r = run_interpreter(program);
         assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), r);
     }
 
@@ -18,8 +19,21 @@ mod interpreter_tests {
         (let y (add-1 x))
         (let z (+ x y))
         (= z (+ x y)))";
-        let r = run_interpreter(&program);
-        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::symbol("True")])), r);
+        let r = run_interpreter(program);
+        // Each let evaluates to the value it bound.
+        let expected = Expr::list(&[
+            Expr::fnum(3.0),
+            Expr::fnum(4.0),
+            Expr::fnum(7.0),
+            Expr::symbol("True"),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), r);
+    }
+
+    #[test]
+    fn if_takes_else_branch_when_predicate_is_false() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(if False 1 2)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(if True 1 2)"));
     }
 
     #[test]
@@ -30,12 +44,1498 @@ mod interpreter_tests {
         (let y (add-1 x))
         (let z (+ x y))
         (= z (+ x y))";
-        match run_interpreter(&program) {
+        match run_interpreter(program) {
             EvalResult::Err(_) => {}
-            _ => assert!(
-                false,
-                "Expected EvalResult::Err resulting from a bad parse."
-            ),
+            _ => panic!("Expected EvalResult::Err resulting from a bad parse."),
+        }
+    }
+
+    #[test]
+    fn char_literal_and_conversions() {
+        assert_eq!(EvalResult::Expr(Expr::character('a')), run_interpreter("#\\a"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(97.0)), run_interpreter("(char->number #\\a)"));
+        assert_eq!(EvalResult::Expr(Expr::character('b')), run_interpreter("(number->char 98)"));
+    }
+
+    #[test]
+    fn string_round_trips_through_char_list() {
+        let chars = Expr::list(&[Expr::character('h'), Expr::character('i')]);
+        assert_eq!(EvalResult::Expr(chars), run_interpreter("(string->list \"hi\")"));
+        assert_eq!(
+            EvalResult::Expr(Expr::string("hello")),
+            run_interpreter("(list->string (string->list \"hello\"))")
+        );
+    }
+
+    #[test]
+    fn ast_size_counts_quoted_nodes() {
+        assert_eq!(
+            EvalResult::Expr(Expr::fnum(7.0)),
+            run_interpreter("(ast-size (quote (+ 1 (* 2 3))))")
+        );
+    }
+
+    #[test]
+    fn ast_as_json() {
+        let expected = concat!(
+            r#"{"type":"list","items":["#,
+            r#"{"type":"symbol","value":"+"},"#,
+            r#"{"type":"number","value":1},"#,
+            r#"{"type":"list","items":["#,
+            r#"{"type":"symbol","value":"*"},"#,
+            r#"{"type":"number","value":2},"#,
+            r#"{"type":"number","value":3}"#,
+            r#"]}]}"#,
+        );
+        assert_eq!(Ok(expected.to_string()), run_interpreter_ast("(+ 1 (* 2 3))"));
+    }
+
+    #[test]
+    fn slice_with_start_and_end() {
+        let expected = Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(slice (quote (1 2 3 4)) 1 3)"));
+    }
+
+    #[test]
+    fn slice_with_negative_start() {
+        let expected = Expr::list(&[Expr::fnum(3.0), Expr::fnum(4.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(slice (quote (1 2 3 4)) -2)"));
+    }
+
+    #[test]
+    fn slice_with_omitted_end_clamps() {
+        let expected = Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0), Expr::fnum(4.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(slice (quote (1 2 3 4)) 1)"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(slice (quote (1 2 3 4)) 9)"));
+    }
+
+    #[test]
+    fn iterate_until_predicate_holds() {
+        let program = "((fn double (x) (* x 2))
+        (fn big (x) (> x 100))
+        (iterate (function double) 1 (function big)))";
+        let expected = Expr::list(&[Expr::fnum(128.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn iterate_hits_iteration_cap() {
+        let program = "((fn same (x) x)
+        (fn never (x) (< x 0))
+        (iterate (function same) 1 (function never)))";
+        match run_interpreter(program) {
+            EvalResult::Err(msg) => assert!(msg.contains("iterations"), "unexpected message: {}", msg),
+            r => panic!("Expected iteration cap error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_error_points_at_unexpected_paren() {
+        let program = "(+ 1\n(- x 2)))";
+        match run_interpreter(program) {
+            EvalResult::Err(msg) => {
+                let lines: Vec<&str> = msg.lines().collect();
+                assert_eq!(4, lines.len(), "unexpected diagnostic: {}", msg);
+                assert!(lines[0].contains("Unexpected ) encountered."));
+                assert_eq!(" --> line 2, column 9", lines[1]);
+                assert_eq!("(- x 2)))", lines[2]);
+                assert_eq!("        ^", lines[3]);
+            },
+            r => panic!("Expected parse error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn empty_list_literal_parses() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(quote ())"));
+    }
+
+    #[test]
+    fn let_values_destructures_divmod() {
+        let program = "(let-values ((q r) (divmod 17 5)) (+ (* q 10) r))";
+        assert_eq!(EvalResult::Expr(Expr::fnum(32.0)), run_interpreter(program));
+    }
+
+    #[test]
+    fn let_values_destructures_values() {
+        let program = "(let-values ((a b) (values 1 2)) (- a b))";
+        assert_eq!(EvalResult::Expr(Expr::fnum(-1.0)), run_interpreter(program));
+    }
+
+    #[test]
+    fn let_values_count_mismatch() {
+        let program = "(let-values ((a b c) (values 1 2)) a)";
+        assert_eq!(
+            EvalResult::Err("let-values: expected 3 values, got 2".into()),
+            run_interpreter(program)
+        );
+    }
+
+    #[test]
+    fn quoted_list_is_a_single_value() {
+        let program = "(let-values ((a b) (quote (#values 1 2))) a)";
+        assert_eq!(
+            EvalResult::Err("let-values: expected 2 values, got 1".into()),
+            run_interpreter(program)
+        );
+        assert_eq!(EvalResult::Expr(Expr::symbol("values")), run_interpreter("(type-of (values 1 2))"));
+    }
+
+    #[test]
+    fn flatten_deeply_nested_list() {
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(3.0), Expr::fnum(4.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(flatten (quote (1 (2 (3 ())) ((4)))))"));
+    }
+
+    #[test]
+    fn flatten_flat_and_empty_lists() {
+        let expected = Expr::list(&[Expr::symbol("a"), Expr::symbol("b")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(flatten (quote (a b)))"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(flatten (quote ()))"));
+    }
+
+    #[test]
+    fn take_fewer_equal_and_more_than_length() {
+        let nums = |xs: &[f64]| EvalResult::Expr(Expr::list(&xs.iter().map(|x| Expr::fnum(*x)).collect::<Vec<_>>()));
+        assert_eq!(nums(&[1.0, 2.0]), run_interpreter("(take 2 (quote (1 2 3)))"));
+        assert_eq!(nums(&[1.0, 2.0, 3.0]), run_interpreter("(take 3 (quote (1 2 3)))"));
+        assert_eq!(nums(&[1.0, 2.0, 3.0]), run_interpreter("(take 7 (quote (1 2 3)))"));
+    }
+
+    #[test]
+    fn drop_fewer_equal_and_more_than_length() {
+        let nums = |xs: &[f64]| EvalResult::Expr(Expr::list(&xs.iter().map(|x| Expr::fnum(*x)).collect::<Vec<_>>()));
+        assert_eq!(nums(&[3.0]), run_interpreter("(drop 2 (quote (1 2 3)))"));
+        assert_eq!(nums(&[]), run_interpreter("(drop 3 (quote (1 2 3)))"));
+        assert_eq!(nums(&[]), run_interpreter("(drop 7 (quote (1 2 3)))"));
+    }
+
+    #[test]
+    fn take_negative_count_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("take: count must not be negative, got -1".into()),
+            run_interpreter("(take -1 (quote (1 2 3)))")
+        );
+    }
+
+    #[test]
+    fn count_multiple_occurrences() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(count 2 (quote (1 2 2 3 2)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(count (quote (a)) (quote ((a) b (a))))"));
+    }
+
+    #[test]
+    fn count_zero_occurrences_and_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.0)), run_interpreter("(count 9 (quote (1 2 3)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.0)), run_interpreter("(count 1 (quote ()))"));
+    }
+
+    #[test]
+    fn enumerate_pairs_index_and_element() {
+        let expected = Expr::list(&[
+            Expr::list(&[Expr::fnum(0.0), Expr::symbol("a")]),
+            Expr::list(&[Expr::fnum(1.0), Expr::symbol("b")]),
+            Expr::list(&[Expr::fnum(2.0), Expr::symbol("c")]),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(enumerate (quote (a b c)))"));
+    }
+
+    #[test]
+    fn enumerate_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(enumerate (quote ()))"));
+    }
+
+    #[test]
+    fn set_rebinds_existing_variable() {
+        let program = "((let x 1) (set! x (+ x 1)) x)";
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(2.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn set_does_not_affect_other_bindings_of_the_same_value() {
+        let program = "((let a (quote (1 2))) (let b a) (set! a 3) b)";
+        let list = Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0)]);
+        let expected = Expr::list(&[list.clone(), list.clone(), Expr::fnum(3.0), list]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn set_unbound_variable_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("cannot set! unbound variable y".into()),
+            run_interpreter("(set! y 1)")
+        );
+    }
+
+    #[test]
+    fn timeout_aborts_infinite_loop() {
+        let start = Instant::now();
+        let r = run_interpreter_with_timeout("(while True (+ 1 1))", Duration::from_millis(50));
+        assert_eq!(EvalResult::Err("evaluation timed out".into()), r);
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn timeout_leaves_quick_programs_alone() {
+        let r = run_interpreter_with_timeout("(+ 1 2)", Duration::from_secs(5));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), r);
+    }
+
+    #[test]
+    fn nan_is_not_equal_to_itself() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(= NaN NaN)"));
+    }
+
+    #[test]
+    fn infinity_equals_itself_and_prints_readably() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(= inf inf)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(= inf -inf)"));
+        let program = "(with-output (print inf -inf NaN))";
+        assert_eq!(EvalResult::Expr(Expr::string("inf -inf NaN")), run_interpreter(program));
+    }
+
+    #[test]
+    fn negative_zero_equals_zero() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(= -0.0 0)"));
+        assert_eq!(EvalResult::Expr(Expr::string("0")), run_interpreter("(with-output (print -0.0))"));
+    }
+
+    #[test]
+    fn delay_does_not_evaluate_until_forced() {
+        let program = "(with-output ((let p (delay ((print forced) 42))) (print before) (let v (force p)) (print v)))";
+        assert_eq!(EvalResult::Expr(Expr::string("before\nforced\n(42)")), run_interpreter(program));
+    }
+
+    #[test]
+    fn force_evaluates_only_once() {
+        let program = "(with-output ((let p (delay ((print forced) 42))) (force p) (force p)))";
+        assert_eq!(EvalResult::Expr(Expr::string("forced")), run_interpreter(program));
+    }
+
+    #[test]
+    fn force_of_plain_value_returns_it() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(force 3)"));
+    }
+
+    #[test]
+    fn error_in_function_body_names_the_function() {
+        let program = "((fn inc (x) (+ x 1)) (inc (quote a)))";
+        assert_eq!(
+            EvalResult::Err("in call to inc: Can only sum numbers, got Symbol(\"a\")".into()),
+            run_interpreter(program)
+        );
+    }
+
+    #[test]
+    fn error_in_nested_call_names_each_function() {
+        let program = "((fn inc (x) (+ x 1)) (fn twice (x) (inc (inc x))) (twice (quote a)))";
+        assert_eq!(
+            EvalResult::Err("in call to twice: in call to inc: Can only sum numbers, got Symbol(\"a\")".into()),
+            run_interpreter(program)
+        );
+    }
+
+    #[test]
+    fn remainder_takes_the_sign_of_the_dividend() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(% 7 5)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(-1.0)), run_interpreter("(% -1 3)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(% 1 -3)"));
+    }
+
+    #[test]
+    fn mod_euclid_is_never_negative() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(mod-euclid 7 5)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(mod-euclid -1 3)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(mod-euclid 1 -3)"));
+    }
+
+    #[test]
+    fn modulo_by_zero_is_an_error() {
+        assert_eq!(EvalResult::Err("%: division by zero".into()), run_interpreter("(% 1 0)"));
+        assert_eq!(EvalResult::Err("mod-euclid: division by zero".into()), run_interpreter("(mod-euclid 1 0)"));
+    }
+
+    #[test]
+    fn swap_macro_exchanges_variables() {
+        let program = "((defmacro swap (a b)
+            (cons (cons (quote let) (cons (quote tmp) (cons a (quote ()))))
+              (cons (cons (quote set!) (cons a (cons b (quote ()))))
+                (cons (cons (quote set!) (cons b (cons (quote tmp) (quote ())))) (quote ())))))
+        (let x 1)
+        (let y 2)
+        (swap x y)
+        (- x y))";
+        match run_interpreter(program) {
+            EvalResult::Expr(e) => assert_eq!(Some(Expr::fnum(1.0)), e.items().and_then(|xs| xs.last().cloned())),
+            r => panic!("Expected a list, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn macro_arguments_are_not_evaluated() {
+        let program = "((defmacro quote-it (x) (cons (quote quote) (cons x (quote ())))) (quote-it (+ 1 2)))";
+        let expected = Expr::list(&[Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)])]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+        let program = "(with-output ((defmacro ignore (x) 0) (ignore (print oops))))";
+        assert_eq!(EvalResult::Expr(Expr::string("")), run_interpreter(program));
+    }
+
+    #[test]
+    fn and_or_test_the_values_of_their_arguments() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(and (= 1 1) True)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(or (= 1 2) (= 1 1))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(and (= 1 1) (quote ()))"));
+    }
+
+    #[test]
+    fn and_or_stop_at_the_deciding_argument() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(and False (car 1))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(or 1 (car 1))"));
+        assert!(matches!(run_interpreter("(and True (car 1))"), EvalResult::Err(_)));
+    }
+
+    #[test]
+    fn computed_head_selects_builtin() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(8.0)), run_interpreter("((if True + -) 5 3)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("((if False + -) 5 3)"));
+    }
+
+    #[test]
+    fn computed_head_selects_user_function() {
+        let program = "((fn double (x) (* x 2)) ((function double) 21))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(42.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn computed_head_yielding_a_plain_symbol_is_not_called() {
+        let program = "((let x (quote hello)) (print x))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::symbol("hello")])), run_interpreter(program));
+        assert_eq!(
+            EvalResult::Err("a is not a function.".into()),
+            run_interpreter("(apply (car (quote (a b))) (quote (1)))")
+        );
+    }
+
+    #[test]
+    fn pprint_keeps_short_list_on_one_line() {
+        let program = "(with-output (pprint (quote (1 (2 3) 4))))";
+        assert_eq!(EvalResult::Expr(Expr::string("(1 (2 3) 4)")), run_interpreter(program));
+    }
+
+    #[test]
+    fn pprint_breaks_long_nested_list() {
+        let program = "(with-output (pprint (quote (define (square x) (multiply x x) (comment long))) 20))";
+        let expected = "(define\n (square x)\n (multiply x x)\n (comment long))";
+        assert_eq!(EvalResult::Expr(Expr::string(expected)), run_interpreter(program));
+    }
+
+    #[test]
+    fn printing_a_self_bound_symbol_marks_the_cycle() {
+        let program = "((let a (quote a)) (with-output (print a)))";
+        let expected = Expr::list(&[Expr::symbol("a"), Expr::string("#0#")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+        let program = "((let b (quote (1 a))) (let a (quote b)) (with-output (print a)))";
+        let expected = Expr::list(&[Expr::list(&[Expr::fnum(1.0), Expr::symbol("a")]), Expr::symbol("b"), Expr::string("(1 #0#)")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn bare_environment_has_no_booleans() {
+        let program = "(car True)";
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter_with_env(program, &mut Environment::default()));
+        match run_interpreter_with_env(program, &mut Environment::bare()) {
+            EvalResult::Err(msg) => assert!(msg.contains("car: expected a list"), "{}", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn bare_environment_accepts_bindings() {
+        let mut env = Environment::bare();
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter_with_env("(let x 2)", &mut env));
+        assert_eq!(EvalResult::Expr(Expr::fnum(4.0)), run_interpreter_with_env("(* x x)", &mut env));
+    }
+
+    #[test]
+    fn length_and_reverse_of_lists() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(length (quote (a b c)))"));
+        let expected = Expr::list(&[Expr::symbol("c"), Expr::symbol("b"), Expr::symbol("a")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(reverse (quote (a b c)))"));
+    }
+
+    #[test]
+    fn list_builtins_reject_non_lists_uniformly() {
+        for op in &["length", "car", "reverse", "flatten"] {
+            assert_eq!(
+                EvalResult::Err(format!("{}: expected a list, got FNum(5.0)", op)),
+                run_interpreter(&format!("({} 5)", op))
+            );
+        }
+        assert_eq!(
+            EvalResult::Err("take: expected a list, got FNum(5.0)".into()),
+            run_interpreter("(take 1 5)")
+        );
+    }
+
+    #[test]
+    fn insert_at_start_middle_and_end() {
+        let syms = |xs: &[&str]| EvalResult::Expr(Expr::list(&xs.iter().map(|x| Expr::symbol(x)).collect::<Vec<_>>()));
+        assert_eq!(syms(&["x", "a", "b"]), run_interpreter("(insert 0 (quote x) (quote (a b)))"));
+        assert_eq!(syms(&["a", "x", "b"]), run_interpreter("(insert 1 (quote x) (quote (a b)))"));
+        assert_eq!(syms(&["a", "b", "x"]), run_interpreter("(insert 2 (quote x) (quote (a b)))"));
+    }
+
+    #[test]
+    fn remove_middle_element() {
+        let expected = Expr::list(&[Expr::symbol("a"), Expr::symbol("c")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(remove 1 (quote (a b c)))"));
+    }
+
+    #[test]
+    fn insert_and_remove_out_of_range() {
+        assert_eq!(
+            EvalResult::Err("insert: index 3 out of range for list of length 2".into()),
+            run_interpreter("(insert 3 (quote x) (quote (a b)))")
+        );
+        assert_eq!(
+            EvalResult::Err("remove: index -1 out of range for list of length 2".into()),
+            run_interpreter("(remove -1 (quote (a b)))")
+        );
+    }
+
+    #[test]
+    fn approx_equal_within_default_epsilon() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(approx= 0.1 0.1000000001)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(approx= 0.1 0.2)"));
+    }
+
+    #[test]
+    fn approx_equal_with_explicit_epsilon() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(approx= 1 1.05 0.1)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(approx= 1 1.5 0.1)"));
+    }
+
+    #[test]
+    fn approx_equal_rejects_non_numbers() {
+        assert_eq!(
+            EvalResult::Err("approx= expects numbers, got Symbol(\"a\")".into()),
+            run_interpreter("(approx= (quote a) 1)")
+        );
+    }
+
+    #[test]
+    fn operator_name_bound_as_variable_reads_as_value() {
+        let program = "(with-output ((let + 5) (print +) (let y (* + 2)) (print y)))";
+        assert_eq!(EvalResult::Expr(Expr::string("5\n10")), run_interpreter(program));
+    }
+
+    #[test]
+    fn operator_name_bound_as_variable_still_calls_operator() {
+        let program = "((let + 5) (+ 1 2))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(5.0), Expr::fnum(3.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn unfold_counts_up_to_five() {
+        let program = "((fn step (n) (values n (+ n 1)))
+        (fn done (n) (> n 5))
+        (unfold (function step) 1 (function done)))";
+        let counted: Vec<_> = (1..=5).map(|n| Expr::fnum(n as f64)).collect();
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::list(&counted)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn unfold_stops_before_first_value() {
+        let program = "((fn step (n) (values n n)) (fn always (n) True) (unfold (function step) 1 (function always)))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::list(&[])])), run_interpreter(program));
+    }
+
+    #[test]
+    fn case_insensitive_program() {
+        let mut env = Environment::default().ignore_case();
+        let program = "((fn Square (X) (* x X)) (let MyVar 3) (square myvar) (car TRUE))";
+        assert_eq!(
+            EvalResult::Expr(Expr::list(&[Expr::fnum(3.0), Expr::fnum(9.0), Expr::fnum(1.0)])),
+            run_interpreter_with_env(program, &mut env)
+        );
+    }
+
+    #[test]
+    fn partition_by_predicate() {
+        let program = "((fn big (x) (> x 2)) (partition (function big) (quote (1 3 2 5 0 4))))";
+        let nums = |xs: &[f64]| Expr::list(&xs.iter().map(|x| Expr::fnum(*x)).collect::<Vec<_>>());
+        let expected = Expr::list(&[Expr::list(&[nums(&[3.0, 5.0, 4.0]), nums(&[1.0, 2.0, 0.0])])]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn partition_empty_list() {
+        let expected = Expr::list(&[Expr::list(&[]), Expr::list(&[])]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(partition not (quote ()))"));
+    }
+
+    #[test]
+    fn partition_propagates_predicate_errors() {
+        let program = "((fn bad (x) (+ x (quote a))) (partition (function bad) (quote (1))))";
+        match run_interpreter(program) {
+            EvalResult::Err(msg) => assert!(msg.contains("Can only sum numbers"), "{}", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn deadline_aborts_infinite_loop() {
+        let start = Instant::now();
+        let r = run_interpreter("(deadline 20 (while True (+ 1 1)))");
+        assert_eq!(EvalResult::Err("deadline exceeded".into()), r);
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn deadline_returns_value_of_quick_body() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(deadline 1000 (+ 1 2))"));
+    }
+
+    #[test]
+    fn host_timeout_wins_over_longer_deadline() {
+        let r = run_interpreter_with_timeout("(deadline 60000 (while True (+ 1 1)))", Duration::from_millis(20));
+        assert_eq!(EvalResult::Err("evaluation timed out".into()), r);
+    }
+
+    #[test]
+    fn every_and_some_over_numbers() {
+        let defs = "(fn pos (x) (> x 0))";
+        let run = |call: &str| run_interpreter(&format!("({} {})", defs, call));
+        let t = EvalResult::Expr(Expr::list(&[Expr::symbol("True")]));
+        let f = EvalResult::Expr(Expr::list(&[Expr::symbol("False")]));
+        assert_eq!(t, run("(every? (function pos) (quote (1 2 3)))"));
+        assert_eq!(f, run("(every? (function pos) (quote (1 -2 3)))"));
+        assert_eq!(t, run("(some? (function pos) (quote (-1 2 -3)))"));
+        assert_eq!(f, run("(some? (function pos) (quote (-1 -2)))"));
+    }
+
+    #[test]
+    fn every_and_some_of_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(every? not (quote ()))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(some? not (quote ()))"));
+    }
+
+    #[test]
+    fn every_and_some_short_circuit() {
+        let program = "(with-output ((fn pos (x) (car ((print x) (> x 0)))) (every? (function pos) (quote (1 -2 3)))))";
+        assert_eq!(EvalResult::Expr(Expr::string("1\n-2")), run_interpreter(program));
+        let program = "(with-output ((fn pos (x) (car ((print x) (> x 0)))) (some? (function pos) (quote (-1 2 3)))))";
+        assert_eq!(EvalResult::Expr(Expr::string("-1\n2")), run_interpreter(program));
+    }
+
+    #[test]
+    fn counter_increments_on_each_call() {
+        let program = "(with-output ((let n 0) (let c (make-counter)) (c) (set! n (c)) (print n)))";
+        assert_eq!(EvalResult::Expr(Expr::string("2")), run_interpreter(program));
+    }
+
+    #[test]
+    fn counters_are_independent() {
+        let program = "(with-output ((let x 0) (let a (make-counter)) (let b (make-counter))
+            (a) (a) (b) (set! x (a)) (let y (b)) (print x y)))";
+        assert_eq!(EvalResult::Expr(Expr::string("3 2")), run_interpreter(program));
+    }
+
+    #[test]
+    fn copies_of_a_counter_share_its_count() {
+        let mut env = Environment {
+            immutable_lets: true,
+            ..Environment::default()
+        };
+        let program = "((let n 0) (let c (make-counter)) (let d c) (c) (d))";
+        let r = run_interpreter_with_env(program, &mut env);
+        match r {
+            EvalResult::Expr(xs) => assert_eq!(Some(Expr::fnum(2.0)), xs.items().and_then(|xs| xs.last().cloned())),
+            r => panic!("Expected a list, got {:?}", r),
         }
     }
+
+    #[test]
+    fn sum_and_product_of_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(10.0)), run_interpreter("(sum (quote (1 2 3 4)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(24.0)), run_interpreter("(product (quote (1 2 3 4)))"));
+    }
+
+    #[test]
+    fn sum_and_product_of_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.0)), run_interpreter("(sum (quote ()))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(product (quote ()))"));
+    }
+
+    #[test]
+    fn sum_reports_index_of_non_number() {
+        assert_eq!(
+            EvalResult::Err("sum: element 1 is not a number, got Symbol(\"a\")".into()),
+            run_interpreter("(sum (quote (1 a 3)))")
+        );
+    }
+
+    #[test]
+    fn defining_special_form_name_records_warning() {
+        let mut env = Environment::default();
+        let r = run_interpreter_with_env("(fn if (a b c) a)", &mut env);
+        assert_eq!(EvalResult::Unit, r);
+        assert_eq!(vec!["if is a special form, so this definition is never called".to_string()], env.warnings);
+    }
+
+    #[test]
+    fn user_functions_shadow_ordinary_builtins() {
+        let mut env = Environment::default();
+        let r = run_interpreter_with_env("((fn map (f xs) 7) (map 1 2))", &mut env);
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(7.0)])), r);
+        assert!(env.warnings.is_empty());
+        let program = "((fn inc (x) (+ x 1)) ((curry map (function inc)) (quote (1 2))))";
+        let expected = Expr::list(&[Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0)])]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+        for name in ["with-output", "deadline"] {
+            assert!(SPECIAL_FORMS.contains(&name), "{} should be a special form", name);
+        }
+    }
+
+    #[test]
+    fn variable_named_after_special_form_records_its_own_warning() {
+        let mut env = Environment::default();
+        let r = run_interpreter_with_env("((let if 5) (+ if 1))", &mut env);
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(5.0), Expr::fnum(6.0)])), r);
+        assert_eq!(vec!["if is a special form, so this variable can be read but not called".to_string()], env.warnings);
+    }
+
+    #[test]
+    fn defining_special_form_name_fails_in_strict_mode() {
+        let mut env = Environment {
+            strict: true,
+            ..Environment::default()
+        };
+        assert_eq!(
+            EvalResult::Err("cannot define if: it is a special form".into()),
+            run_interpreter_with_env("(fn if (a b c) a)", &mut env)
+        );
+        assert_eq!(
+            EvalResult::Err("cannot define quote: it is a special form".into()),
+            run_interpreter_with_env("(let quote 1)", &mut env)
+        );
+        assert!(env.warnings.is_empty());
+    }
+
+    #[test]
+    fn format_substitutes_arguments() {
+        assert_eq!(EvalResult::Expr(Expr::string("x=1 y=2")), run_interpreter("(format \"x={} y={}\" 1 (+ 1 1))"));
+        assert_eq!(
+            EvalResult::Expr(Expr::string("list: (a 1.5)")),
+            run_interpreter("(format \"list: {}\" (quote (a 1.5)))")
+        );
+    }
+
+    #[test]
+    fn format_argument_count_mismatch() {
+        assert_eq!(EvalResult::Err("format: too few arguments, got 1".into()), run_interpreter("(format \"{} {}\" 1)"));
+        assert_eq!(EvalResult::Err("format: too many arguments, got 2".into()), run_interpreter("(format \"{}\" 1 2)"));
+    }
+
+    #[test]
+    fn format_escaped_braces() {
+        assert_eq!(EvalResult::Expr(Expr::string("{1}")), run_interpreter("(format \"{{{}}}\" 1)"));
+    }
+
+    #[test]
+    fn tree_approx_equal_nested_within_epsilon() {
+        let program = "(tree-approx= (quote ((1 2) (3 (4.0000000001)))) (quote ((1 2) (3 (4)))))";
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter(program));
+        let program = "(tree-approx= (quote ((1 2) (3 4.2))) (quote ((1 2) (3 4))) 0.5)";
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter(program));
+    }
+
+    #[test]
+    fn tree_approx_equal_different_shapes() {
+        let program = "(tree-approx= (quote ((1 2) 3)) (quote ((1 2 3))))";
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter(program));
+        let program = "(tree-approx= (quote (1 (2))) (quote (1 2)))";
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter(program));
+    }
+
+    #[test]
+    fn depth_counts_contexts() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(depth)"));
+        let program = "((fn outer (x) (+ x (depth))) (outer 0))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(2.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn depth_tracks_recursion() {
+        let program = "((fn down (n) (if (= n 0) (depth) (down (- n 1)))) (down 3))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(5.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn operators_are_values() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(6.0)), run_interpreter("(reduce + 0 (quote (1 2 3)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(24.0)), run_interpreter("(reduce * 1 (quote (2 3 4)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(7.0)), run_interpreter("(reduce - 10 (quote (1 2)))"));
+    }
+
+    #[test]
+    fn printing_operators_shows_their_names() {
+        assert_eq!(EvalResult::Expr(Expr::string("(+ 1 2)")), run_interpreter("(with-output (print (+ 1 2)))"));
+        assert_eq!(EvalResult::Expr(Expr::string("+")), run_interpreter("(with-output (print +))"));
+    }
+
+    #[test]
+    fn operators_still_take_many_arguments() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(10.0)), run_interpreter("(+ 1 2 3 4)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(< 1 2 3)"));
+    }
+
+    #[test]
+    fn reduce_with_user_fn() {
+        let program = "((fn larger (a b) (if (> a b) a b)) (reduce (function larger) 0 (quote (3 9 4))))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(9.0)])), run_interpreter(program));
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), run_interpreter("(reduce + 5 (quote ()))"));
+    }
+
+    #[test]
+    fn chunk_evenly_divisible() {
+        let expected = Expr::list(&[
+            Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0)]),
+            Expr::list(&[Expr::fnum(3.0), Expr::fnum(4.0)]),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(chunk 2 (quote (1 2 3 4)))"));
+    }
+
+    #[test]
+    fn chunk_with_remainder() {
+        let expected = Expr::list(&[
+            Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0)]),
+            Expr::list(&[Expr::fnum(3.0), Expr::fnum(4.0)]),
+            Expr::list(&[Expr::fnum(5.0)]),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(chunk 2 (quote (1 2 3 4 5)))"));
+    }
+
+    #[test]
+    fn chunk_empty_list_and_bad_size() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(chunk 3 (quote ()))"));
+        assert_eq!(
+            EvalResult::Err("chunk: size must be positive, got 0".into()),
+            run_interpreter("(chunk 0 (quote (1 2)))")
+        );
+    }
+
+    #[test]
+    fn distinct_removes_adjacent_and_later_duplicates() {
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::list(&[Expr::fnum(3.0)])]);
+        assert_eq!(
+            EvalResult::Expr(expected),
+            run_interpreter("(distinct (quote (1 1 2 (3) 1 2 (3))))")
+        );
+    }
+
+    #[test]
+    fn distinct_keeps_unique_list() {
+        let expected = Expr::list(&[Expr::fnum(3.0), Expr::fnum(1.0), Expr::fnum(2.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(distinct (quote (3 1 2)))"));
+    }
+
+    #[test]
+    fn product_pairs_of_two_lists() {
+        let pair = |x: f64, y: &str| Expr::list(&[Expr::fnum(x), Expr::symbol(y)]);
+        let expected = Expr::list(&[pair(1.0, "a"), pair(1.0, "b"), pair(2.0, "a"), pair(2.0, "b")]);
+        assert_eq!(
+            EvalResult::Expr(expected),
+            run_interpreter("(product-pairs (quote (1 2)) (quote (a b)))")
+        );
+    }
+
+    #[test]
+    fn product_pairs_with_empty_operand() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(product-pairs (quote ()) (quote (a b)))"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(product-pairs (quote (1 2)) (quote ()))"));
+    }
+
+    #[test]
+    fn printing_self_referential_promise_terminates() {
+        let program = "(with-output ((let p (delay p)) (let same (force p)) (print same)))";
+        assert_eq!(EvalResult::Expr(Expr::string("<promise>")), run_interpreter(program));
+    }
+
+    #[test]
+    fn alist_round_trips_through_map() {
+        let alist = Expr::list(&[
+            Expr::list(&[Expr::symbol("a"), Expr::fnum(1.0)]),
+            Expr::list(&[Expr::symbol("b"), Expr::fnum(2.0)]),
+        ]);
+        assert_eq!(
+            EvalResult::Expr(alist),
+            run_interpreter("(map->alist (alist->map (quote ((a 1) (b 2)))))")
+        );
+    }
+
+    #[test]
+    fn alist_to_map_last_duplicate_wins() {
+        let alist = Expr::list(&[
+            Expr::list(&[Expr::symbol("a"), Expr::fnum(3.0)]),
+            Expr::list(&[Expr::symbol("b"), Expr::fnum(2.0)]),
+        ]);
+        assert_eq!(
+            EvalResult::Expr(alist),
+            run_interpreter("(map->alist (alist->map (quote ((a 1) (b 2) (a 3)))))")
+        );
+    }
+
+    #[test]
+    fn alist_to_map_rejects_non_pairs() {
+        assert_eq!(
+            EvalResult::Err("alist->map: element 1 is not a (key value) pair, got FNum(2.0)".into()),
+            run_interpreter("(alist->map (quote ((a 1) 2)))")
+        );
+    }
+
+    #[test]
+    fn minmax_of_numbers() {
+        assert_eq!(
+            EvalResult::Expr(Expr::list(&[Expr::fnum(-2.0), Expr::fnum(7.5)])),
+            run_interpreter("(minmax (quote (3 -2 7.5 0)))")
+        );
+    }
+
+    #[test]
+    fn minmax_errors() {
+        assert_eq!(
+            EvalResult::Err("minmax: empty list has no minimum or maximum".into()),
+            run_interpreter("(minmax (quote ()))")
+        );
+        assert_eq!(
+            EvalResult::Err("minmax: element 1 is not a number, got Symbol(\"a\")".into()),
+            run_interpreter("(minmax (quote (1 a)))")
+        );
+    }
+
+    #[test]
+    fn starts_with_prefix() {
+        assert_eq!(
+            EvalResult::Expr(Expr::symbol("True")),
+            run_interpreter("(starts-with? (quote (1 (2))) (quote (1 (2) 3)))")
+        );
+        assert_eq!(
+            EvalResult::Expr(Expr::symbol("False")),
+            run_interpreter("(starts-with? (quote (2 3)) (quote (1 2 3)))")
+        );
+    }
+
+    #[test]
+    fn ends_with_suffix() {
+        assert_eq!(
+            EvalResult::Expr(Expr::symbol("True")),
+            run_interpreter("(ends-with? (quote (2 3)) (quote (1 2 3)))")
+        );
+        assert_eq!(
+            EvalResult::Expr(Expr::symbol("False")),
+            run_interpreter("(ends-with? (quote (1 2 3 4)) (quote (2 3 4)))")
+        );
+    }
+
+    #[test]
+    fn empty_list_is_prefix_and_suffix() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(starts-with? (quote ()) (quote (1)))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(ends-with? (quote ()) (quote ()))"));
+    }
+
+    #[test]
+    fn read_eval_string_evaluates_source() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(read-eval-string \"(+ 1 2)\")"));
+        let program = "((let x 4) (read-eval-string \"(* x x)\"))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(4.0), Expr::fnum(16.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn read_eval_string_reports_syntax_errors() {
+        assert_eq!(
+            EvalResult::Err("read-eval-string: Parse error: Incomplete".into()),
+            run_interpreter("(read-eval-string \"(+ 1\")")
+        );
+        assert_eq!(
+            EvalResult::Err("read-eval-string: Lex error: UnterminatedString".into()),
+            run_interpreter("(read-eval-string \"(print \\\"oops)\")")
+        );
+    }
+
+    #[test]
+    fn curry_builtin_operator() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(15.0)), run_interpreter("((curry + 10) 5)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), run_interpreter("((curry - 10) 5)"));
+    }
+
+    #[test]
+    fn curry_user_function() {
+        let program = "((fn scale (k x) (* k x)) (map (curry (function scale) 3) (quote (1 2))) ((curry (function scale) 2) 5))";
+        let expected = Expr::list(&[Expr::list(&[Expr::fnum(3.0), Expr::fnum(6.0)]), Expr::fnum(10.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn compose_applies_right_to_left() {
+        let program = "((fn inc (x) (+ x 1)) (fn dbl (x) (* x 2)) ((compose (function inc) (function dbl)) 5) ((compose (function dbl) (function inc)) 5))";
+        assert_eq!(
+            EvalResult::Expr(Expr::list(&[Expr::fnum(11.0), Expr::fnum(12.0)])),
+            run_interpreter(program)
+        );
+    }
+
+    #[test]
+    fn compose_three_functions_with_operators() {
+        let program = "((fn inc (x) (+ x 1)) ((compose (curry * 3) (function inc) (curry - 10)) 4))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(21.0)])), run_interpreter(program));
+        assert_eq!(
+            EvalResult::Err("compose: expected a function, got FNum(1.0)".into()),
+            run_interpreter("(compose 1 car)")
+        );
+    }
+
+    #[test]
+    fn repeat_value() {
+        let expected = Expr::list(&[Expr::fnum(0.0), Expr::fnum(0.0), Expr::fnum(0.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(repeat 3 0)"));
+        let pair = Expr::list(&[Expr::symbol("a"), Expr::symbol("b")]);
+        assert_eq!(
+            EvalResult::Expr(Expr::list(&[pair.clone(), pair])),
+            run_interpreter("(repeat 2 (quote (a b)))")
+        );
+    }
+
+    #[test]
+    fn repeat_zero_and_negative() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(repeat 0 1)"));
+        assert_eq!(
+            EvalResult::Err("repeat: count must not be negative, got -2".into()),
+            run_interpreter("(repeat -2 1)")
+        );
+    }
+
+    #[test]
+    fn repeat_rejects_huge_counts() {
+        match run_interpreter("(repeat 1e300 0)") {
+            EvalResult::Err(msg) => assert!(msg.starts_with("repeat: count must be at most 1000000"), "{}", msg),
+            r => panic!("Expected error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn string_to_number_or_parses() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(-12.5)), run_interpreter("(string->number-or \" -12.5\" 0)"));
+    }
+
+    #[test]
+    fn string_to_number_or_falls_back_to_default() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.0)), run_interpreter("(string->number-or \"abc\" 0)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(string->number-or \"\" (+ 1 2))"));
+    }
+
+    #[test]
+    fn bound_after_let() {
+        let program = "((let x 1) (bound? (quote x)) (bound? (quote True)))";
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::symbol("True"), Expr::symbol("True")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn unbound_symbol_is_not_bound() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(bound? (quote nope))"));
+        assert_eq!(
+            EvalResult::Err("bound?: expected a symbol, got FNum(1.0)".into()),
+            run_interpreter("(bound? 1)")
+        );
+    }
+
+    #[test]
+    fn operators_are_bound() {
+        let program = "((bound? (quote +)) (bound? (quote <=)))";
+        let expected = Expr::list(&[Expr::symbol("True"), Expr::symbol("True")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn undef_removes_binding() {
+        let program = "((let x 1) (undef x) (bound? (quote x)))";
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::symbol("False")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn undef_unbound_variable_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("cannot undef unbound variable y".into()),
+            run_interpreter("(undef y)")
+        );
+    }
+
+    #[test]
+    fn gcd_and_lcm_of_two() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(6.0)), run_interpreter("(gcd 12 18)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(36.0)), run_interpreter("(lcm 12 18)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(4.0)), run_interpreter("(gcd -8 12)"));
+    }
+
+    #[test]
+    fn gcd_and_lcm_of_many() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), run_interpreter("(gcd 30 45 (+ 10 10))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(60.0)), run_interpreter("(lcm 3 4 5 6)"));
+    }
+
+    #[test]
+    fn gcd_rejects_non_integers() {
+        assert_eq!(
+            EvalResult::Err("gcd: expected an integer, got FNum(1.5)".into()),
+            run_interpreter("(gcd 3 1.5)")
+        );
+    }
+
+    #[test]
+    fn gcd_and_lcm_report_overflow() {
+        assert_eq!(
+            EvalResult::Err("gcd: result overflows a 64-bit integer".into()),
+            run_interpreter("(gcd -1e300 -1)")
+        );
+        assert_eq!(
+            EvalResult::Err("lcm: result overflows a 64-bit integer".into()),
+            run_interpreter("(lcm 1e19 3)")
+        );
+    }
+
+    #[test]
+    fn divides_divisible_and_not() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(divides? 3 12)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(divides? -4 8)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(divides? 5 12)"));
+    }
+
+    #[test]
+    fn divides_at_the_edge_of_the_integer_range() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(divides? -1 -1e300)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(divides? -1e300 -1)"));
+    }
+
+    #[test]
+    fn divides_by_zero_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("divides?: divisor must not be zero".into()),
+            run_interpreter("(divides? 0 12)")
+        );
+        assert!(matches!(run_interpreter("(divides? 2 2.5)"), EvalResult::Err(_)));
+    }
+
+    #[test]
+    fn factorial_and_expt() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(120.0)), run_interpreter("(factorial 5)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(factorial 0)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1024.0)), run_interpreter("(expt 2 10)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.25)), run_interpreter("(expt 0.5 2)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(expt 7 0)"));
+    }
+
+    #[test]
+    fn factorial_and_expt_reject_negative_arguments() {
+        assert_eq!(
+            EvalResult::Err("factorial: expected a non-negative integer, got -3".into()),
+            run_interpreter("(factorial -3)")
+        );
+        assert_eq!(
+            EvalResult::Err("expt: expected a non-negative integer, got -1".into()),
+            run_interpreter("(expt 2 -1)")
+        );
+    }
+
+    #[test]
+    fn factorial_overflow_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("factorial: result of (factorial 200) overflows".into()),
+            run_interpreter("(factorial 200)")
+        );
+    }
+
+    #[test]
+    fn values_of_bound_names() {
+        let program = "((let a 1) (let b (quote (2 3))) (values-of (quote a) (quote b)))";
+        let expected = Expr::list(&[
+            Expr::fnum(1.0),
+            Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0)]),
+            Expr::list(&[Expr::fnum(1.0), Expr::list(&[Expr::fnum(2.0), Expr::fnum(3.0)])]),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn values_of_unbound_name() {
+        let program = "((let a 1) (values-of (quote a) (quote missing)))";
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::list(&[Expr::fnum(1.0), Expr::list(&[])])]);
+        assert_eq!(EvalResult::Expr(expected.clone()), run_interpreter(program));
+
+        let mut env = Environment { strict: true, ..Environment::default() };
+        assert_eq!(EvalResult::Expr(expected), run_interpreter_with_env(program, &mut env));
+
+        let mut env = Environment { strict_values_of: true, ..Environment::default() };
+        assert_eq!(
+            EvalResult::Err("values-of: unbound variable missing".into()),
+            run_interpreter_with_env(program, &mut env)
+        );
+    }
+
+    #[test]
+    fn print_each_prints_one_line_per_element() {
+        let program = "(with-output (print-each (quote (1 \"two\" (3 4)))))";
+        assert_eq!(EvalResult::Expr(Expr::string("1\ntwo\n(3 4)")), run_interpreter(program));
+    }
+
+    #[test]
+    fn print_each_requires_a_list() {
+        assert_eq!(
+            EvalResult::Err("print-each: expected a list, got FNum(1.0)".into()),
+            run_interpreter("(print-each 1)")
+        );
+    }
+
+    #[test]
+    fn paths_of_flat_list() {
+        let entry = |i: f64, x: &str| Expr::list(&[Expr::list(&[Expr::fnum(i)]), Expr::symbol(x)]);
+        let expected = Expr::list(&[entry(0.0, "a"), entry(1.0, "b")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(paths (quote (a b)))"));
+    }
+
+    #[test]
+    fn paths_of_nested_list() {
+        let entry = |path: &[f64], x: &str| {
+            let path: Vec<_> = path.iter().map(|&i| Expr::fnum(i)).collect();
+            Expr::list(&[Expr::list(&path), Expr::symbol(x)])
+        };
+        let expected = Expr::list(&[entry(&[0.0], "a"), entry(&[1.0, 0.0], "b"), entry(&[1.0, 1.0], "c")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(paths (quote (a (b c))))"));
+    }
+
+    #[test]
+    fn two_armed_if_when_truthy() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(if (< 1 2) 1)"));
+    }
+
+    #[test]
+    fn two_armed_if_when_falsey_is_unit() {
+        assert_eq!(EvalResult::Unit, run_interpreter("(if (> 1 2) 1)"));
+        let program = "((let x 5) (if (> x 9) (print big)) x)";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(5.0), Expr::fnum(5.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn three_armed_if_and_too_many_arms() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(if (> 1 2) 1 2)"));
+        assert!(matches!(run_interpreter("(if True 1 2 3)"), EvalResult::Err(_)));
+    }
+
+    #[test]
+    fn list_depth_of_atom_and_flat_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.0)), run_interpreter("(list-depth 5)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(list-depth (quote (a b c)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(list-depth (quote ()))"));
+    }
+
+    #[test]
+    fn list_depth_of_nested_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), run_interpreter("(list-depth (quote (a (b))))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(list-depth (quote ((a) (b (c)) d)))"));
+    }
+
+    #[test]
+    fn car_or_of_non_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(car-or (quote (1 2)) 0)"));
+    }
+
+    #[test]
+    fn car_or_of_empty_list_gives_default() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), run_interpreter("(car-or (quote ()) (+ 1 2))"));
+        assert_eq!(
+            EvalResult::Err("car-or: expected a list, got FNum(4.0)".into()),
+            run_interpreter("(car-or 4 0)")
+        );
+    }
+
+    #[test]
+    fn set_on_let_binding_fails_when_lets_are_immutable() {
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        assert_eq!(
+            EvalResult::Err("cannot set! immutable binding x, define it with var instead".into()),
+            run_interpreter_with_env("((let x 1) (set! x 2))", &mut env)
+        );
+    }
+
+    #[test]
+    fn set_on_var_binding_succeeds_when_lets_are_immutable() {
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        assert_eq!(
+            EvalResult::Expr(Expr::list(&[Expr::fnum(1.0), Expr::fnum(2.0), Expr::fnum(2.0)])),
+            run_interpreter_with_env("((var x 1) (set! x 2) x)", &mut env)
+        );
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        assert!(matches!(
+            run_interpreter_with_env("((var x 1) (let x 3) (set! x 2))", &mut env),
+            EvalResult::Err(_)
+        ));
+    }
+
+    #[test]
+    fn redefining_an_undefined_var_drops_its_mutability() {
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        assert_eq!(
+            EvalResult::Err("cannot set! immutable binding x, define it with var instead".into()),
+            run_interpreter_with_env("((var x 1) (undef x) (fn x (a) a) (set! x 5))", &mut env)
+        );
+        let mut env = Environment { immutable_lets: true, ..Environment::default() };
+        assert!(matches!(
+            run_interpreter_with_env("((var x 1) (undef x) (let x 2) (set! x 5))", &mut env),
+            EvalResult::Err(_)
+        ));
+    }
+
+    #[test]
+    fn type_of_atoms() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("number")), run_interpreter("(type-of 1.5)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("symbol")), run_interpreter("(type-of (quote a))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("string")), run_interpreter("(type-of \"a\")"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("char")), run_interpreter("(type-of #\\a)"));
+    }
+
+    #[test]
+    fn type_of_compound_values() {
+        assert_eq!(EvalResult::Expr(Expr::symbol("list")), run_interpreter("(type-of (quote (1 2)))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("list")), run_interpreter("(type-of (cons 1 (quote ())))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("function")), run_interpreter("(type-of +)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("function")), run_interpreter("(type-of (curry + 1))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("promise")), run_interpreter("(type-of (delay 1))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("function")), run_interpreter("(type-of (make-counter))"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("map")), run_interpreter("(type-of (alist->map (quote ())))"));
+    }
+
+    #[test]
+    fn swap_two_elements() {
+        let expected = Expr::list(&[Expr::symbol("c"), Expr::symbol("b"), Expr::symbol("a")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(swap 0 2 (quote (a b c)))"));
+    }
+
+    #[test]
+    fn swap_element_with_itself() {
+        let expected = Expr::list(&[Expr::symbol("a"), Expr::symbol("b")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(swap 1 1 (quote (a b)))"));
+    }
+
+    #[test]
+    fn swap_out_of_range_index() {
+        assert_eq!(
+            EvalResult::Err("swap: index 3 out of range for list of length 3".into()),
+            run_interpreter("(swap 0 3 (quote (a b c)))")
+        );
+    }
+
+    #[test]
+    fn fold_until_stops_early() {
+        let program = "(with-output ((fn add (acc x) (car ((print x) (+ acc x)))) \
+            (fn big (acc) (> acc 3)) \
+            (let r (fold-until (function add) 0 (quote (1 2 3 4 5)) (function big))) (print r)))";
+        assert_eq!(EvalResult::Expr(Expr::string("1\n2\n3\n6")), run_interpreter(program));
+    }
+
+    #[test]
+    fn fold_until_folds_whole_list_when_never_stopped() {
+        let program = "((fn big (acc) (> acc 100)) (fold-until + 0 (quote (1 2 3 4 5)) (function big)))";
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(15.0)])), run_interpreter(program));
+    }
+
+    #[test]
+    fn interleave_equal_lengths() {
+        let expected = Expr::list(&[
+            Expr::fnum(1.0), Expr::symbol("a"),
+            Expr::fnum(2.0), Expr::symbol("b"),
+            Expr::fnum(3.0), Expr::symbol("c"),
+        ]);
+        assert_eq!(
+            EvalResult::Expr(expected),
+            run_interpreter("(interleave (quote (1 2 3)) (quote (a b c)))")
+        );
+    }
+
+    #[test]
+    fn interleave_stops_at_shorter_list() {
+        let expected = Expr::list(&[Expr::fnum(1.0), Expr::symbol("a")]);
+        assert_eq!(EvalResult::Expr(expected.clone()), run_interpreter("(interleave (quote (1 2 3)) (quote (a)))"));
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(interleave (quote (1)) (quote (a b)))"));
+    }
+
+    #[test]
+    fn lookup_variable_binding() {
+        let program = "((let x (+ 1 2)) (lookup (quote x)) (lookup (quote missing)))";
+        let expected = Expr::list(&[
+            Expr::fnum(3.0),
+            Expr::list(&[Expr::list(&[]), Expr::fnum(3.0)]),
+            Expr::list(&[]),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn lookup_function_binding() {
+        let program = "((fn add (a b) (+ a b)) (lookup (quote add)))";
+        let body = Expr::list(&[Expr::symbol("+"), Expr::symbol("a"), Expr::symbol("b")]);
+        let expected = Expr::list(&[Expr::list(&[Expr::symbol("a"), Expr::symbol("b")]), body]);
+        assert_eq!(EvalResult::Expr(Expr::list(&[expected])), run_interpreter(program));
+    }
+
+    #[test]
+    fn load_defines_functions_from_file() {
+        let path = std::env::temp_dir().join(format!("mlisp-load-{}.mlisp", std::process::id()));
+        std::fs::write(&path, "(fn square (x) (* x x))\n(let loaded 1)\n(square 3)\n").unwrap();
+        let mut env = Environment::default();
+        let program = format!("(load \"{}\")", path.display());
+        assert_eq!(EvalResult::Expr(Expr::fnum(9.0)), run_interpreter_with_env(&program, &mut env));
+        assert_eq!(EvalResult::Expr(Expr::fnum(16.0)), run_interpreter_with_env("(square 4)", &mut env));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_errors_name_the_file() {
+        let path = std::env::temp_dir().join(format!("mlisp-load-bad-{}.mlisp", std::process::id()));
+        std::fs::write(&path, "(fn square (x) (* x x)\n").unwrap();
+        let program = format!("(load \"{}\")", path.display());
+        let expected = format!("load {}: Parse error: Incomplete", path.display());
+        assert_eq!(EvalResult::Err(expected), run_interpreter(&program));
+        std::fs::remove_file(&path).unwrap();
+
+        match run_interpreter("(load \"/no/such/file.mlisp\")") {
+            EvalResult::Err(err) => assert!(err.starts_with("load /no/such/file.mlisp: "), "{}", err),
+            r => panic!("expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn frequencies_in_first_seen_order() {
+        let pair = |x: &str, n: f64| Expr::list(&[Expr::symbol(x), Expr::fnum(n)]);
+        let expected = Expr::list(&[pair("a", 3.0), pair("b", 2.0), pair("c", 1.0)]);
+        assert_eq!(
+            EvalResult::Expr(expected),
+            run_interpreter("(frequencies (quote (a b a c b a)))")
+        );
+    }
+
+    #[test]
+    fn frequencies_of_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(frequencies (quote ()))"));
+    }
+
+    #[test]
+    fn scan_running_sum() {
+        let expected = Expr::list(&[Expr::fnum(0.0), Expr::fnum(1.0), Expr::fnum(3.0), Expr::fnum(6.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(scan + 0 (quote (1 2 3)))"));
+    }
+
+    #[test]
+    fn scan_of_empty_list_and_errors() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::fnum(5.0)])), run_interpreter("(scan + 5 (quote ()))"));
+        assert_eq!(
+            EvalResult::Err("car: expected a list, got FNum(1.0)".into()),
+            run_interpreter("((fn bad (acc x) (car x)) (scan (function bad) 0 (quote (1))))")
+        );
+    }
+
+    #[test]
+    fn unit_is_not_the_empty_list() {
+        assert_eq!(EvalResult::Unit, run_interpreter("unit"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("True")), run_interpreter("(unit? unit)"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(unit? ())"));
+        assert_eq!(EvalResult::Expr(Expr::symbol("False")), run_interpreter("(unit? (quote ()))"));
+    }
+
+    #[test]
+    fn print_returns_unit() {
+        let program = "(with-output ((let u (unit? (print 1))) (print u)))";
+        assert_eq!(EvalResult::Expr(Expr::string("1\n(1)")), run_interpreter(program));
+    }
+
+    #[test]
+    fn transpose_square() {
+        let row = |a: f64, b: f64| Expr::list(&[Expr::fnum(a), Expr::fnum(b)]);
+        let expected = Expr::list(&[row(1.0, 3.0), row(2.0, 4.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(transpose (quote ((1 2) (3 4))))"));
+    }
+
+    #[test]
+    fn transpose_rectangular() {
+        let row = |a: f64, b: f64| Expr::list(&[Expr::fnum(a), Expr::fnum(b)]);
+        let expected = Expr::list(&[row(1.0, 4.0), row(2.0, 5.0), row(3.0, 6.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(transpose (quote ((1 2 3) (4 5 6))))"));
+    }
+
+    #[test]
+    fn transpose_ragged_rows_is_an_error() {
+        assert_eq!(
+            EvalResult::Err("transpose: row 1 has length 1, expected 2".into()),
+            run_interpreter("(transpose (quote ((1 2) (3))))")
+        );
+    }
+
+    #[test]
+    fn index_of_present_element() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), run_interpreter("(index-of (quote (b)) (quote (a (b) c (b))))"));
+    }
+
+    #[test]
+    fn index_of_absent_element_and_empty_list() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(-1.0)), run_interpreter("(index-of 4 (quote (1 2 3)))"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(-1.0)), run_interpreter("(index-of 4 (quote ()))"));
+    }
+
+    #[test]
+    fn map_pairs_of_three_elements() {
+        let expected = Expr::list(&[Expr::fnum(3.0), Expr::fnum(2.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(map-pairs - (quote (10 7 5)))"));
+    }
+
+    #[test]
+    fn map_pairs_of_short_lists() {
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(map-pairs - (quote ()))"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(map-pairs - (quote (1)))"));
+    }
+
+    #[test]
+    fn map_indexed_passes_index_and_element() {
+        let expected = Expr::list(&[Expr::fnum(10.0), Expr::fnum(21.0), Expr::fnum(32.0)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("(map-indexed + (quote (10 20 30)))"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(map-indexed + (quote ()))"));
+    }
+
+    #[test]
+    fn map_indexed_propagates_errors() {
+        assert_eq!(
+            EvalResult::Err("car: expected a list, got FNum(0.0)".into()),
+            run_interpreter("((fn bad (i x) (car i)) (map-indexed (function bad) (quote (1))))")
+        );
+    }
+
+    #[test]
+    fn named_let_sums_one_to_a_hundred() {
+        let program = "(let loop ((i 1) (acc 0)) (if (> i 100) acc (loop (+ i 1) (+ acc i))))";
+        assert_eq!(EvalResult::Expr(Expr::fnum(5050.0)), run_interpreter(program));
+    }
+
+    #[test]
+    fn named_let_tail_calls_do_not_grow_the_stack() {
+        let program = "(let loop ((i 1) (acc 0)) (if (> i 20000) acc (loop (+ i 1) (+ acc i))))";
+        assert_eq!(EvalResult::Expr(Expr::fnum(200010000.0)), run_interpreter(program));
+    }
+
+    #[test]
+    fn named_let_is_local_to_its_body() {
+        let program = "((let n 2) (let twice ((x n)) (* x 2)) (bound? (quote twice)))";
+        let expected = Expr::list(&[Expr::fnum(2.0), Expr::fnum(4.0), Expr::symbol("False")]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+        assert!(matches!(run_interpreter("(let loop (i 0) i)"), EvalResult::Err(_)));
+    }
 }
@@ -1,11 +1,11 @@
 #[cfg(test)]
 mod lex_tests {
-    use mlisp::lex::{lex, Token};
+    use mlisp::lex::{lex, LexError, Token};
 
     #[test]
     fn can_lex_lpar() {
         lex("(").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
                 assert_eq!(Token::LPar, tokens[0]);
@@ -16,7 +16,7 @@ mod lex_tests {
     #[test]
     fn can_lex_rpar() {
         lex(")").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
                 assert_eq!(Token::RPar, tokens[0]);
@@ -27,7 +27,7 @@ mod lex_tests {
     #[test]
     fn can_lex_literal() {
         lex("hello-world").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
                 assert_eq!(Token::Literal("hello-world".into()), tokens[0]);
@@ -48,7 +48,7 @@ mod lex_tests {
 
         match lex(input) {
             Ok(actual) => assert_eq!(output, actual),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
@@ -70,15 +70,58 @@ mod lex_tests {
 
         match lex(input) {
             Ok(actual) => assert_eq!(output, actual),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
     #[test]
     fn lex_empty_string() {
         lex("").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| assert_eq!(0, tokens.len()),
         );
     }
+
+    #[test]
+    fn lex_string_literal() {
+        let input = "(print \"hello world\" \"say \\\"hi\\\"\")";
+        let output = vec![
+            Token::LPar,
+            Token::Literal("print".into()),
+            Token::Str("hello world".into()),
+            Token::Str("say \"hi\"".into()),
+            Token::RPar,
+        ];
+
+        match lex(input) {
+            Ok(actual) => assert_eq!(output, actual),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn lex_unterminated_string() {
+        match lex("(print \"oops)") {
+            Err(LexError::UnterminatedString) => {}
+            r => panic!("Expected UnterminatedString, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn lex_char_literal() {
+        let input = "(list #\\a #\\( #\\space)";
+        let output = vec![
+            Token::LPar,
+            Token::Literal("list".into()),
+            Token::Char('a'),
+            Token::Char('('),
+            Token::Char(' '),
+            Token::RPar,
+        ];
+
+        match lex(input) {
+            Ok(actual) => assert_eq!(output, actual),
+            r => panic!("{:?}", r),
+        }
+    }
 }
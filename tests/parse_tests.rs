@@ -1,13 +1,13 @@
 #[cfg(test)]
 mod parse_tests {
-    use mlisp::lex::Token;
-    use mlisp::parse::parse;
+    use mlisp::lex::{lex, lex_spanned, Token};
+    use mlisp::parse::{check_balance, parse, parse_program, parse_spanned, BalanceError, ParseError};
     use mlisp::types::Expr;
 
     #[test]
     fn parse_fnum() {
         parse(&[Token::Literal("1".into())]).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(Expr::fnum(1.0), expr),
         );
     }
@@ -15,11 +15,19 @@ mod parse_tests {
     #[test]
     fn parse_symbol() {
         parse(&[Token::Literal("hello".into())]).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(Expr::symbol("hello"), expr),
         )
     }
 
+    #[test]
+    fn parse_string() {
+        parse(&[Token::Str("hello world".into())]).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(Expr::string("hello world"), expr),
+        )
+    }
+
     #[test]
     fn parse_list() {
         let tokens = [
@@ -32,7 +40,7 @@ mod parse_tests {
         let expected = Expr::list(&[Expr::symbol("+"), Expr::fnum(1.8), Expr::fnum(1.2)]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
@@ -50,7 +58,7 @@ mod parse_tests {
         let expected = Expr::list(&[Expr::list(&[Expr::symbol("A")])]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
@@ -80,8 +88,105 @@ mod parse_tests {
         ]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
+
+    #[test]
+    fn trailing_tokens_are_an_error() {
+        let tokens = [
+            Token::LPar,
+            Token::Literal("+".into()),
+            Token::Literal("1".into()),
+            Token::Literal("2".into()),
+            Token::RPar,
+            Token::Literal("garbage".into()),
+        ];
+
+        match parse(&tokens) {
+            Err(ParseError::BadParse(msg)) => assert_eq!("unexpected trailing tokens", msg),
+            r => panic!("Expected BadParse, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_program_reads_every_form() {
+        let tokens = [
+            Token::LPar,
+            Token::Literal("+".into()),
+            Token::Literal("1".into()),
+            Token::Literal("2".into()),
+            Token::RPar,
+            Token::Literal("garbage".into()),
+        ];
+        let expected = vec![
+            Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]),
+            Expr::symbol("garbage"),
+        ];
+
+        parse_program(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |exprs| assert_eq!(expected, exprs),
+        );
+    }
+
+    #[test]
+    fn parse_spanned_reports_inner_form_span() {
+        let source = "(+ 1 (* 2 3))";
+        let tree = parse_spanned(&lex_spanned(source).unwrap()).unwrap();
+        assert_eq!("(+ 1 (* 2 3))", &source[tree.span.start..tree.span.end]);
+        let inner = &tree.children[2];
+        assert_eq!("(* 2 3)", &source[inner.span.start..inner.span.end]);
+        assert_eq!(Expr::list(&[Expr::symbol("*"), Expr::fnum(2.0), Expr::fnum(3.0)]), inner.expr);
+    }
+
+    #[test]
+    fn enclosing_finds_innermost_form() {
+        let source = "(+ 1 (* 2 3))";
+        let tree = parse_spanned(&lex_spanned(source).unwrap()).unwrap();
+        let at_two = tree.enclosing(8).unwrap();
+        assert_eq!(Expr::fnum(2.0), at_two.expr);
+        let at_space = tree.enclosing(7).unwrap();
+        assert_eq!("(* 2 3)", &source[at_space.span.start..at_space.span.end]);
+        assert!(tree.enclosing(20).is_none());
+    }
+
+    #[test]
+    fn unclosed_list_is_incomplete() {
+        let tokens = [Token::LPar, Token::Literal("+".into()), Token::Literal("1".into())];
+        match parse(&tokens) {
+            Err(ParseError::Incomplete) => {},
+            r => panic!("Expected Incomplete, got {:?}", r),
+        }
+        let nested = [Token::LPar, Token::Literal("f".into()), Token::LPar, Token::RPar];
+        assert!(matches!(parse_program(&nested), Err(ParseError::Incomplete)));
+    }
+
+    #[test]
+    fn unexpected_close_is_a_bad_parse() {
+        match parse(&[Token::RPar]) {
+            Err(ParseError::BadParse(msg)) => assert_eq!("Unexpected ) encountered.", msg),
+            r => panic!("Expected BadParse, got {:?}", r),
+        }
+        let tokens = [Token::LPar, Token::RPar, Token::RPar, Token::LPar];
+        assert!(matches!(parse_program(&tokens), Err(ParseError::BadParse(_))));
+    }
+
+
+    #[test]
+    fn check_balance_reports_stray_rpar() {
+        let tokens = lex("(+ 1 2)) (f)").unwrap();
+        assert_eq!(vec![(5, BalanceError::Unexpected)], check_balance(&tokens));
+    }
+
+    #[test]
+    fn check_balance_reports_every_unclosed_lpar() {
+        let tokens = lex("(f (g 1)\n(h (i 2)").unwrap();
+        assert_eq!(
+            vec![(0, BalanceError::Unclosed), (6, BalanceError::Unclosed)],
+            check_balance(&tokens)
+        );
+        assert!(check_balance(&lex("(a (b) ())").unwrap()).is_empty());
+    }
 }
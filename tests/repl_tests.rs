@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod repl_tests {
+    use mlisp::eval::EvalResult;
+    use mlisp::repl::{is_incomplete, Repl};
+    use mlisp::types::Expr;
+
+    #[test]
+    fn underscore_holds_previous_result() {
+        let mut repl = Repl::default();
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), repl.eval_line("(+ 1 2)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(30.0)), repl.eval_line("(* _ 10)"));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.0)), repl.eval_line("_2"));
+    }
+
+    #[test]
+    fn run_prints_results() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(+ 1 2)\n(print _)\n(* _ 2)\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> 3\n> 3\n> 6\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn env_command_lists_bindings() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(let x 5)\n(fn inc (n) (+ n 1))\n:env\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("x = 5\n"), "{}", output);
+        assert!(output.contains("inc = <func-object: inc (n)>\n"), "{}", output);
+    }
+
+    #[test]
+    fn env_command_lists_operators() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run(":env\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("+ = <func-object: lambda (a b)>\n"), "{}", output);
+    }
+
+    #[test]
+    fn reset_command_clears_bindings() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(let x 5)\n:reset\n:env\n".as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("x = "), "{}", output);
+        assert!(output.contains("True = (1)"), "{}", output);
+    }
+
+    #[test]
+    fn quit_command_stops_reading() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(+ 1 2)\n:quit\n(+ 3 4)\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> 3\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn unknown_command_is_reported() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run(":frobnicate\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> Error: unknown command :frobnicate, try :help\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn run_prints_warnings() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(fn if (a) a)\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            "> Warning: if is a special form, so this definition is never called\n> ",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn multi_line_form_evaluates_once_closed() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(+ 1\n(* 2\n3))\n_\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> .. .. 7\n> 7\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn string_spanning_lines_is_continued() {
+        let mut repl = Repl::default();
+        let mut output = Vec::new();
+        repl.run("(print \"a\nb\")\n".as_bytes(), &mut output).unwrap();
+        assert_eq!("> .. a\nb\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn incomplete_input_detection() {
+        assert!(is_incomplete("(+ 1"));
+        assert!(is_incomplete("(print \"abc"));
+        assert!(!is_incomplete("(+ 1 2)"));
+        assert!(!is_incomplete(")"));
+        assert!(!is_incomplete("(list #\\()"));
+    }
+}